@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, FileType};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// directories with more entries than this aren't cached
+const MAX_CACHED_DIR_ENTRIES: usize = 100_000;
+/// if the cache holds more entries than this, it is cleared
+const MAX_CACHED_ENTRIES: usize = 1_000_000;
+/// directories modified less than this long ago aren't cached, because on file systems
+/// with coarse timestamps, another change could happen without changing the mtime.
+const RACY_MTIME: Duration = Duration::from_secs(2);
+
+/// a directory's entries, as returned by `fs::read_dir`
+pub(crate) type DirEntries = Arc<Vec<(OsString, Option<FileType>)>>;
+
+/// Directory listings shared by all instances, so that listing a directory
+/// another instance has already listed doesn't need to read it again.
+/// A listing is only used if the directory's mtime hasn't changed since it was cached.
+#[derive(Default)]
+pub(crate) struct ScanCache {
+    dirs: HashMap<PathBuf, (SystemTime, DirEntries)>,
+    total_entries: usize,
+}
+pub(crate) type SharedScanCache = Arc<Mutex<ScanCache>>;
+
+impl ScanCache {
+    pub fn clear(&mut self) {
+        self.dirs.clear();
+        self.total_entries = 0;
+    }
+    fn get(&self, dir: &Path, mtime: SystemTime) -> Option<DirEntries> {
+        self.dirs
+            .get(dir)
+            .filter(|(cached_mtime, _)| *cached_mtime == mtime)
+            .map(|(_, entries)| Arc::clone(entries))
+    }
+    fn insert(&mut self, dir: PathBuf, mtime: SystemTime, entries: DirEntries) {
+        if self.total_entries + entries.len() > MAX_CACHED_ENTRIES {
+            self.clear();
+        }
+        self.total_entries += entries.len();
+        if let Some((_, old)) = self.dirs.insert(dir, (mtime, entries)) {
+            self.total_entries -= old.len();
+        }
+    }
+}
+
+/// lists `dir`, using the cache if possible.
+/// if the directory is too large to be cached, the entries are read while iterating.
+pub(crate) fn read_dir_cached(
+    cache: &SharedScanCache,
+    dir: &Path,
+) -> io::Result<Box<dyn Iterator<Item = (OsString, Option<FileType>)>>> {
+    let mtime = fs::metadata(dir).and_then(|v| v.modified()).ok();
+    if let Some(mtime) = mtime {
+        if let Some(entries) = cache.lock().unwrap().get(dir, mtime) {
+            return Ok(Box::new(
+                (0..entries.len()).map(move |i| entries[i].clone()),
+            ));
+        }
+    }
+    let mut files = fs::read_dir(dir)?
+        .flatten()
+        .map(|e| (e.file_name(), e.file_type().ok()));
+    let mut entries = vec![];
+    for entry in files.by_ref() {
+        entries.push(entry);
+        if entries.len() > MAX_CACHED_DIR_ENTRIES {
+            // too large, read the rest while iterating
+            return Ok(Box::new(entries.into_iter().chain(files)));
+        }
+    }
+    let entries = Arc::new(entries);
+    if let Some(mtime) = mtime {
+        if mtime.elapsed().is_ok_and(|v| v > RACY_MTIME) {
+            cache
+                .lock()
+                .unwrap()
+                .insert(dir.to_owned(), mtime, Arc::clone(&entries));
+        }
+    }
+    Ok(Box::new(
+        (0..entries.len()).map(move |i| entries[i].clone()),
+    ))
+}
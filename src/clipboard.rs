@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+/// Entries yanked or cut with Y or U, which C pastes in any instance.
+pub(crate) struct Clipboard {
+    /// if true, pasting moves the entries instead of copying them
+    pub cut: bool,
+    pub entries: Vec<ClipboardEntry>,
+}
+
+pub(crate) struct ClipboardEntry {
+    /// the directory the entry was listed in
+    pub parent: PathBuf,
+    /// the entry's path, relative to `parent`
+    pub rel_path: PathBuf,
+    /// true for directories which are copied with everything inside them, see `tasks::selected_entries`
+    pub recursive: bool,
+}
+
+impl Clipboard {
+    /// the entries grouped by the directory they were listed in, like `tasks::task_copy` expects them
+    pub fn sources(&self) -> Vec<(PathBuf, Vec<(PathBuf, bool)>)> {
+        let mut sources: Vec<(PathBuf, Vec<(PathBuf, bool)>)> = vec![];
+        for entry in &self.entries {
+            let item = (entry.rel_path.clone(), entry.recursive);
+            match sources.last_mut() {
+                Some((parent, items)) if *parent == entry.parent => items.push(item),
+                _ => sources.push((entry.parent.clone(), vec![item])),
+            }
+        }
+        sources
+    }
+}
@@ -0,0 +1,126 @@
+use std::{fs, io, path::PathBuf};
+
+use crate::filter::FilterSyntax;
+use crate::query;
+use crate::Layout;
+
+/// Settings loaded from the config file.
+///
+/// The file is read from `$XDG_CONFIG_HOME/tuifile/config` (or `~/.config/tuifile/config`).
+/// Each line is `key = value`, empty lines and lines starting with `#` are ignored.
+/// Keys which may appear more than once (like `exclude`) add to a list.
+#[derive(Default)]
+pub(crate) struct Config {
+    /// `show_hidden = true|false`
+    pub show_hidden: Option<bool>,
+    /// `exclude = <glob>`
+    pub exclude: Vec<String>,
+    /// `one_file_system = true|false`
+    pub one_file_system: Option<bool>,
+    /// `entry_limit = <number>`
+    pub entry_limit: Option<usize>,
+    /// `filter_syntax = regex|glob|fuzzy|substring|query|content`
+    pub filter_syntax: Option<FilterSyntax>,
+    /// `content_search_max_size = <size>`, like `10M`
+    pub content_search_max_size: Option<u64>,
+    /// `filter_paths = true|false`
+    pub filter_paths: Option<bool>,
+    /// `filter_ancestors = true|false`
+    pub filter_ancestors: Option<bool>,
+    /// `history_size = <number>`
+    pub history_size: Option<usize>,
+    /// `preview = true|false`
+    pub preview: Option<bool>,
+    /// `preview_width = <percent>`
+    pub preview_width: Option<u16>,
+    /// `theme = <name>`, read from `themes/<name>` next to the config file
+    pub theme: Option<String>,
+    /// `ls_colors = true|false`
+    pub ls_colors: Option<bool>,
+    /// `layout = single|columns|rows`
+    pub layout: Option<Layout>,
+    /// `tab_bar = true|false`
+    pub tab_bar: Option<bool>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            Some(PathBuf::from(dir).join("tuifile").join("config"))
+        } else {
+            std::env::var_os("HOME")
+                .filter(|v| !v.is_empty())
+                .map(|home| PathBuf::from(home).join(".config/tuifile/config"))
+        }
+    }
+    /// loads the config file. if the file doesn't exist, the default config is returned.
+    pub fn load(path: Option<&PathBuf>) -> Result<Self, String> {
+        let path = match path {
+            Some(v) => v,
+            None => return Ok(Self::default()),
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        let mut config = Self::default();
+        for (line_nr, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("{}:{}: {msg}", path.display(), line_nr + 1);
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => return Err(err(format!("expected 'key = value', found '{line}'"))),
+            };
+            match key {
+                "show_hidden" => config.show_hidden = Some(parse_bool(value).map_err(err)?),
+                "exclude" => config.exclude.push(value.to_owned()),
+                "one_file_system" => config.one_file_system = Some(parse_bool(value).map_err(err)?),
+                "entry_limit" => {
+                    config.entry_limit = Some(value.parse().map_err(|e| err(format!("{e}")))?)
+                }
+                "filter_syntax" => match FilterSyntax::parse(value) {
+                    Some(v) => config.filter_syntax = Some(v),
+                    None => return Err(err(format!("unknown filter syntax '{value}'"))),
+                },
+                "filter_paths" => config.filter_paths = Some(parse_bool(value).map_err(err)?),
+                "filter_ancestors" => {
+                    config.filter_ancestors = Some(parse_bool(value).map_err(err)?)
+                }
+                "history_size" => {
+                    config.history_size = Some(value.parse().map_err(|e| err(format!("{e}")))?)
+                }
+                "preview" => config.preview = Some(parse_bool(value).map_err(err)?),
+                "preview_width" => match value.parse() {
+                    Ok(v @ 10..=90) => config.preview_width = Some(v),
+                    Ok(_) => return Err(err("expected a width between 10 and 90".to_owned())),
+                    Err(e) => return Err(err(format!("{e}"))),
+                },
+                "theme" => config.theme = Some(value.to_owned()),
+                "ls_colors" => config.ls_colors = Some(parse_bool(value).map_err(err)?),
+                "layout" => match Layout::parse(value) {
+                    Some(v) => config.layout = Some(v),
+                    None => return Err(err(format!("unknown layout '{value}'"))),
+                },
+                "tab_bar" => config.tab_bar = Some(parse_bool(value).map_err(err)?),
+                "content_search_max_size" => {
+                    config.content_search_max_size =
+                        Some(query::parse_size(value).map_err(|(_, e)| err(e))?)
+                }
+                _ => return Err(err(format!("unknown key '{key}'"))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected true or false, found '{value}'")),
+    }
+}
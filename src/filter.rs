@@ -0,0 +1,498 @@
+use std::fmt::Display;
+use std::fs::Metadata;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::glob::Glob;
+use crate::query::Query;
+
+/// How the text in the search bar is interpreted.
+/// The default can be changed with Tab, and a prefix (like `g:`) overrides it.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum FilterSyntax {
+    #[default]
+    Regex,
+    Glob,
+    Fuzzy,
+    Substring,
+    /// see `Query`
+    Query,
+    /// a regex which the file's contents have to match (see `ContentSearch`)
+    Content,
+}
+impl FilterSyntax {
+    pub const ALL: [Self; 6] = [
+        Self::Regex,
+        Self::Glob,
+        Self::Fuzzy,
+        Self::Substring,
+        Self::Query,
+        Self::Content,
+    ];
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|v| *v == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+    /// the prefix which selects this syntax, like `g:` for glob
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Regex => "r:",
+            Self::Glob => "g:",
+            Self::Fuzzy => "f:",
+            Self::Substring => "s:",
+            Self::Query => "q:",
+            Self::Content => "c:",
+        }
+    }
+    /// removes the prefix from `text` if it has one.
+    /// returns the syntax selected by the prefix, or `self` if there is none.
+    pub fn apply_prefix(self, text: &str) -> (Self, &str) {
+        for syntax in Self::ALL {
+            if let Some(rest) = text.strip_prefix(syntax.prefix()) {
+                return (syntax, rest);
+            }
+        }
+        (self, text)
+    }
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.to_string() == value)
+    }
+}
+impl Display for FilterSyntax {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Regex => write!(f, "regex"),
+            Self::Glob => write!(f, "glob"),
+            Self::Fuzzy => write!(f, "fuzzy"),
+            Self::Substring => write!(f, "substring"),
+            Self::Query => write!(f, "query"),
+            Self::Content => write!(f, "content"),
+        }
+    }
+}
+
+/// A compiled search bar filter. Matching is case-insensitive
+/// (for fuzzy matching, only if the query is all lowercase).
+#[derive(Clone)]
+pub(crate) enum Filter {
+    Regex(Regex),
+    Glob(Glob),
+    Fuzzy { query: Vec<char>, ignore_case: bool },
+    Substring(String),
+    Query(Query),
+    Content(Regex),
+}
+/// why the search text couldn't be compiled
+#[derive(Clone, Debug)]
+pub(crate) struct FilterError {
+    /// index of the char (in the text passed to `Filter::new`) where the error is, if known
+    pub position: Option<usize>,
+    pub message: String,
+}
+impl Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some(pos) => write!(f, "error at position {}: {}", pos + 1, self.message),
+            None => write!(f, "error: {}", self.message),
+        }
+    }
+}
+impl From<String> for FilterError {
+    fn from(message: String) -> Self {
+        Self {
+            position: None,
+            message,
+        }
+    }
+}
+impl From<regex::Error> for FilterError {
+    /// syntax errors are multi-line messages with the pattern, a line with `^` below the error's position,
+    /// and a line starting with `error: `. this extracts the position and the last line.
+    fn from(e: regex::Error) -> Self {
+        let text = e.to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+        let message = lines.iter().find_map(|line| line.strip_prefix("error: "));
+        let position = lines
+            .iter()
+            .find(|line| {
+                let line = line.trim();
+                !line.is_empty() && line.chars().all(|c| c == '^')
+            })
+            .and_then(|line| line.chars().position(|c| c == '^'))
+            // the pattern is indented by 4 spaces
+            .and_then(|col| col.checked_sub(4));
+        match message {
+            Some(message) => Self {
+                position,
+                message: message.to_owned(),
+            },
+            None => Self {
+                position: None,
+                message: text.lines().collect::<Vec<_>>().join(" "),
+            },
+        }
+    }
+}
+
+/// the result of matching a name against a filter
+pub(crate) struct FilterMatch {
+    /// only used for ranking in fuzzy mode, higher is better
+    pub score: i64,
+    /// indices of the chars (not bytes) which should be highlighted
+    pub highlight: Vec<usize>,
+}
+
+impl Filter {
+    pub fn new(syntax: FilterSyntax, text: &str) -> Result<Self, FilterError> {
+        Ok(match syntax {
+            FilterSyntax::Regex => {
+                Self::Regex(RegexBuilder::new(text).case_insensitive(true).build()?)
+            }
+            FilterSyntax::Glob => Self::Glob(Glob::new(&text.to_lowercase())?),
+            FilterSyntax::Fuzzy => Self::Fuzzy {
+                ignore_case: !text.chars().any(char::is_uppercase),
+                query: text.chars().filter(|ch| !ch.is_whitespace()).collect(),
+            },
+            FilterSyntax::Substring => Self::Substring(text.to_lowercase()),
+            FilterSyntax::Query => Self::Query(Query::new(text)?),
+            FilterSyntax::Content => {
+                Self::Content(RegexBuilder::new(text).case_insensitive(true).build()?)
+            }
+        })
+    }
+    /// true if the entries should be sorted by their score
+    pub fn ranked(&self) -> bool {
+        matches!(self, Self::Fuzzy { .. })
+    }
+    /// Some if the files' contents, not their names, have to match this regex
+    pub fn content_regex(&self) -> Option<&Regex> {
+        match self {
+            Self::Content(regex) => Some(regex),
+            _ => None,
+        }
+    }
+    /// true if `find` needs the entries' metadata
+    pub fn needs_metadata(&self) -> bool {
+        matches!(self, Self::Query(_))
+    }
+    pub fn find(&self, name: &str, metadata: Option<&Metadata>) -> Option<FilterMatch> {
+        match self {
+            Self::Regex(regex) => regex.find(name).map(|m| FilterMatch {
+                score: 0,
+                highlight: char_range(name, m.start(), m.end()),
+            }),
+            Self::Glob(glob) => {
+                // the '/' at the end of directory names isn't part of the name
+                let name = name.strip_suffix('/').unwrap_or(name).to_lowercase();
+                glob.is_match(&name).then(|| FilterMatch {
+                    score: 0,
+                    highlight: vec![],
+                })
+            }
+            Self::Fuzzy { query, ignore_case } => fuzzy_match(query, name, *ignore_case),
+            Self::Substring(text) => {
+                let lower = name.to_lowercase();
+                let start = lower.find(text.as_str())?;
+                // lowercasing can change the length of a string, so this only highlights if it didn't.
+                Some(FilterMatch {
+                    score: 0,
+                    highlight: if lower.len() == name.len() {
+                        char_range(name, start, start + text.len())
+                    } else {
+                        vec![]
+                    },
+                })
+            }
+            Self::Query(query) => query.matches(name, metadata).then(|| FilterMatch {
+                score: 0,
+                highlight: vec![],
+            }),
+            // uses the results of the `ContentSearch` instead
+            Self::Content(_) => None,
+        }
+    }
+}
+
+/// A filter compiled from a search text, either the one being typed or one on the filter stack.
+/// A leading `!` negates it, followed by an optional prefix which selects the syntax.
+#[derive(Clone)]
+pub(crate) struct SearchFilter {
+    pub negated: bool,
+    syntax: FilterSyntax,
+    /// the text without `!` and the syntax prefix
+    pattern: String,
+    filter: Filter,
+}
+impl SearchFilter {
+    /// `syntax` is used if `text` doesn't start with a prefix
+    pub fn new(syntax: FilterSyntax, text: &str) -> Result<Self, FilterError> {
+        let (negated, rest) = match text.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (syntax, pattern) = syntax.apply_prefix(rest);
+        let prefix_len = text.len() - pattern.len();
+        let filter = Filter::new(syntax, pattern).map_err(|mut e| {
+            if let Some(pos) = &mut e.position {
+                *pos += prefix_len;
+            }
+            e
+        })?;
+        if negated && filter.content_regex().is_some() {
+            return Err(FilterError {
+                position: Some(0),
+                message: "content searches can't be negated".to_owned(),
+            });
+        }
+        Ok(Self {
+            negated,
+            syntax,
+            pattern: pattern.to_owned(),
+            filter,
+        })
+    }
+    /// the text this filter was created from, always with the prefix for its syntax
+    pub fn label(&self) -> String {
+        format!(
+            "{}{}{}",
+            if self.negated { "!" } else { "" },
+            self.syntax.prefix(),
+            self.pattern
+        )
+    }
+    pub fn content_regex(&self) -> Option<&Regex> {
+        self.filter.content_regex()
+    }
+    pub fn needs_metadata(&self) -> bool {
+        self.filter.needs_metadata()
+    }
+    /// negated filters aren't ranked, all entries which pass them are equally good
+    pub fn ranked(&self) -> bool {
+        !self.negated && self.filter.ranked()
+    }
+    pub fn find(&self, name: &str, metadata: Option<&Metadata>) -> Option<FilterMatch> {
+        if self.negated {
+            self.filter
+                .find(name, metadata)
+                .is_none()
+                .then(|| FilterMatch {
+                    score: 0,
+                    highlight: vec![],
+                })
+        } else {
+            self.filter.find(name, metadata)
+        }
+    }
+}
+
+/// converts a range of bytes to the indices of the chars in that range
+fn char_range(text: &str, start: usize, end: usize) -> Vec<usize> {
+    text.char_indices()
+        .enumerate()
+        .filter(|(_, (byte, _))| start <= *byte && *byte < end)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// fzf-style fuzzy matching: all chars of the query have to appear in the name, in order.
+/// matches at the start of words and consecutive matches score higher, gaps between matches score lower.
+/// finds the best-scoring way to match the query.
+fn fuzzy_match(query: &[char], name: &str, ignore_case: bool) -> Option<FilterMatch> {
+    let chars = name.chars().collect::<Vec<_>>();
+    if query.is_empty() {
+        return Some(FilterMatch {
+            score: 0,
+            highlight: vec![],
+        });
+    }
+    if query.len() > chars.len() {
+        return None;
+    }
+    let eq = |a: char, b: char| {
+        if ignore_case {
+            a == b || a.to_lowercase().eq(b.to_lowercase())
+        } else {
+            a == b
+        }
+    };
+    let bonus = |j: usize| -> i64 {
+        match j.checked_sub(1).map(|p| chars[p]) {
+            None | Some('/' | '_' | '-' | '.' | ' ') => BONUS_BOUNDARY,
+            Some(prev) if prev.is_lowercase() && chars[j].is_uppercase() => BONUS_CAMEL,
+            _ => 0,
+        }
+    };
+    // score[i][j]: best score with query[..=i] matched and query[i] matched at chars[j]
+    // from[i][j]: where query[i-1] was matched in that case
+    let mut score = vec![vec![None::<i64>; chars.len()]; query.len()];
+    let mut from = vec![vec![0; chars.len()]; query.len()];
+    for (i, q) in query.iter().enumerate() {
+        // best value of score[i-1][k] - (gap penalty from k to j), for k < j - 1
+        // the gap penalty is PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (j - k - 2)
+        let mut best_gap: Option<(i64, usize)> = None;
+        for j in 0..chars.len() {
+            if j >= 2 && i > 0 {
+                if let Some(prev) = score[i - 1][j - 2] {
+                    // shift the previous best by one more char of gap
+                    let new = prev - PENALTY_GAP_START;
+                    best_gap = match best_gap {
+                        Some((v, k)) if v - PENALTY_GAP_EXTENSION >= new => {
+                            Some((v - PENALTY_GAP_EXTENSION, k))
+                        }
+                        _ => Some((new, j - 2)),
+                    };
+                } else if let Some((v, k)) = best_gap {
+                    best_gap = Some((v - PENALTY_GAP_EXTENSION, k));
+                }
+            }
+            if !eq(*q, chars[j]) {
+                continue;
+            }
+            let base = SCORE_MATCH + bonus(j);
+            if i == 0 {
+                score[i][j] = Some(base);
+                continue;
+            }
+            let consecutive = j
+                .checked_sub(1)
+                .and_then(|k| score[i - 1][k].map(|v| (v + BONUS_CONSECUTIVE, k)));
+            let best = match (consecutive, best_gap) {
+                (Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+                (a, b) => a.or(b),
+            };
+            if let Some((v, k)) = best {
+                score[i][j] = Some(v + base);
+                from[i][j] = k;
+            }
+        }
+    }
+    let last = query.len() - 1;
+    let (mut j, best) = score[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, v)| v.map(|v| (j, v)))
+        .max_by_key(|(j, v)| (*v, std::cmp::Reverse(*j)))?;
+    let mut highlight = vec![j];
+    for i in (1..=last).rev() {
+        j = from[i][j];
+        highlight.push(j);
+    }
+    highlight.reverse();
+    Some(FilterMatch {
+        score: best,
+        highlight,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fuzzy_match, Filter, FilterSyntax, SearchFilter};
+
+    fn fuzzy(query: &str, name: &str) -> Option<Vec<usize>> {
+        let query = query.chars().collect::<Vec<_>>();
+        fuzzy_match(&query, name, true).map(|m| m.highlight)
+    }
+    fn score(query: &str, name: &str) -> i64 {
+        let query = query.chars().collect::<Vec<_>>();
+        fuzzy_match(&query, name, true).unwrap().score
+    }
+    fn highlight(syntax: FilterSyntax, text: &str, name: &str) -> Option<Vec<usize>> {
+        let filter = Filter::new(syntax, text).unwrap();
+        filter.find(name, None).map(|m| m.highlight)
+    }
+
+    #[test]
+    fn fuzzy_matches() {
+        assert_eq!(fuzzy("fb", "foo_bar"), Some(vec![0, 4]));
+        assert_eq!(fuzzy("abc", "xaxbxc"), Some(vec![1, 3, 5]));
+        assert_eq!(fuzzy("ba", "ab"), None);
+        assert_eq!(fuzzy("abc", "ab"), None);
+        assert_eq!(fuzzy("", "ab"), Some(vec![]));
+        assert_eq!(fuzzy("AB", "xaxb"), Some(vec![1, 3]));
+        // the highlight is in chars, not bytes
+        assert_eq!(fuzzy("éa", "xéxa"), Some(vec![1, 3]));
+        assert_eq!(fuzzy_match(&['B'], "ab", false).map(|m| m.highlight), None);
+    }
+
+    #[test]
+    fn fuzzy_scores() {
+        // the start of words
+        assert_eq!(fuzzy("mr", "main.rs"), Some(vec![0, 5]));
+        assert!(score("mr", "main.rs") > score("mr", "mirror"));
+        assert_eq!(fuzzy("fb", "fooBar"), Some(vec![0, 3]));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+        // consecutive chars
+        assert!(score("ab", "ab") > score("ab", "axb"));
+        // shorter gaps
+        assert!(score("ab", "axb") > score("ab", "axxxb"));
+        // the best match, not the first one
+        assert_eq!(fuzzy("ab", "axxxx_ab"), Some(vec![6, 7]));
+    }
+
+    #[test]
+    fn syntaxes() {
+        assert_eq!(
+            highlight(FilterSyntax::Regex, "a.", "ÉaB"),
+            Some(vec![1, 2])
+        );
+        assert_eq!(highlight(FilterSyntax::Regex, "^b", "ab"), None);
+        assert_eq!(
+            highlight(FilterSyntax::Substring, "AB", "xÉab"),
+            Some(vec![2, 3])
+        );
+        assert_eq!(highlight(FilterSyntax::Substring, "ba", "ab"), None);
+        // case-insensitive, and the `/` of directories isn't part of the name
+        assert_eq!(
+            highlight(FilterSyntax::Glob, "*.RS", "Main.rs"),
+            Some(vec![])
+        );
+        assert_eq!(highlight(FilterSyntax::Glob, "d*r", "dir/"), Some(vec![]));
+        assert_eq!(highlight(FilterSyntax::Glob, "*.rs", "main.c"), None);
+        // smart case, and spaces are ignored
+        assert_eq!(
+            highlight(FilterSyntax::Fuzzy, "a b", "AxB"),
+            Some(vec![0, 2])
+        );
+        assert_eq!(highlight(FilterSyntax::Fuzzy, "aB", "ab"), None);
+        assert!(Filter::new(FilterSyntax::Glob, "[a").is_err());
+    }
+
+    #[test]
+    fn prefixes() {
+        assert!(FilterSyntax::Regex.apply_prefix("g:*.rs") == (FilterSyntax::Glob, "*.rs"));
+        assert!(FilterSyntax::Fuzzy.apply_prefix("ab") == (FilterSyntax::Fuzzy, "ab"));
+        assert!(FilterSyntax::parse("substring") == Some(FilterSyntax::Substring));
+    }
+
+    #[test]
+    fn search_filters() {
+        let filter = SearchFilter::new(FilterSyntax::Regex, "!g:*.rs").unwrap();
+        assert!(filter.negated);
+        assert_eq!(filter.label(), "!g:*.rs");
+        assert!(filter.find("main.rs", None).is_none());
+        assert!(filter.find("main.c", None).is_some());
+        let filter = SearchFilter::new(FilterSyntax::Fuzzy, "ab").unwrap();
+        assert_eq!(filter.label(), "f:ab");
+        assert!(filter.ranked());
+        assert!(!SearchFilter::new(FilterSyntax::Fuzzy, "!ab")
+            .unwrap()
+            .ranked());
+        // error positions include the `!` and the prefix
+        let error = SearchFilter::new(FilterSyntax::Regex, "!r:a(")
+            .err()
+            .unwrap();
+        assert_eq!(error.position, Some(4));
+        let error = SearchFilter::new(FilterSyntax::Regex, "!c:a")
+            .err()
+            .unwrap();
+        assert_eq!(error.position, Some(0));
+        assert!(highlight(FilterSyntax::Content, "a", "a").is_none());
+    }
+}
@@ -0,0 +1,238 @@
+/// A shell-style glob pattern.
+///
+/// - `*` matches any number of characters except `/`
+/// - `**` matches any number of characters, including `/`
+/// - `?` matches exactly one character except `/`
+/// - `[abc]`, `[a-z]` and `[!a-z]` match one character from (or not from) a set
+/// - `\` escapes the next character
+#[derive(Clone, Debug)]
+pub(crate) struct Glob {
+    pattern: String,
+    tokens: Vec<Token>,
+}
+#[derive(Clone, Debug)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    DoubleStar,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut tokens = vec![];
+        let mut chars = pattern.chars().peekable();
+        while let Some(ch) = chars.next() {
+            tokens.push(match ch {
+                '*' => {
+                    if chars.peek() == Some(&'*') {
+                        chars.next();
+                        Token::DoubleStar
+                    } else {
+                        Token::Star
+                    }
+                }
+                '?' => Token::Any,
+                '\\' => match chars.next() {
+                    Some(ch) => Token::Char(ch),
+                    None => return Err(format!("'{pattern}': trailing backslash")),
+                },
+                '[' => {
+                    let negated = matches!(chars.peek(), Some('!' | '^'));
+                    if negated {
+                        chars.next();
+                    }
+                    let mut ranges = vec![];
+                    let mut first = true;
+                    loop {
+                        let start = match chars.next() {
+                            Some(']') if !first => break,
+                            Some('\\') => chars.next(),
+                            v => v,
+                        };
+                        let start = match start {
+                            Some(v) => v,
+                            None => return Err(format!("'{pattern}': unclosed '['")),
+                        };
+                        first = false;
+                        let mut end = start;
+                        if chars.peek() == Some(&'-') {
+                            chars.next();
+                            match chars.next() {
+                                Some(']') => {
+                                    ranges.push((start, start));
+                                    ranges.push(('-', '-'));
+                                    break;
+                                }
+                                Some(v) => end = v,
+                                None => return Err(format!("'{pattern}': unclosed '['")),
+                            }
+                        }
+                        ranges.push((start, end));
+                    }
+                    Token::Class { negated, ranges }
+                }
+                ch => Token::Char(ch),
+            });
+        }
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            tokens,
+        })
+    }
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+    /// true if the pattern contains a `/`, meaning it should be matched against a path rather than a file name.
+    pub fn matches_paths(&self) -> bool {
+        self.pattern.contains('/')
+    }
+    /// a failed match only backtracks to the last `*` (or, once that can't go on, the last `**`),
+    /// so this doesn't take exponentially long for patterns like `*a*a*a*b`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text = text.chars().collect::<Vec<_>>();
+        let (mut t, mut i) = (0, 0);
+        // the token after the last `*` or `**`, and the index of the first char it doesn't match yet
+        let mut star: Option<(usize, usize)> = None;
+        let mut double_star: Option<(usize, usize)> = None;
+        loop {
+            match self.tokens.get(t) {
+                Some(Token::Star) => {
+                    star = Some((t + 1, i));
+                    t += 1;
+                    continue;
+                }
+                Some(Token::DoubleStar) => {
+                    // the `*` before it never has to match more, this one can
+                    star = None;
+                    double_star = Some((t + 1, i));
+                    t += 1;
+                    continue;
+                }
+                Some(token) if text.get(i).is_some_and(|ch| token.matches(*ch)) => {
+                    t += 1;
+                    i += 1;
+                    continue;
+                }
+                None if i == text.len() => return true,
+                _ => {}
+            }
+            // the last `*` matches one more char, unless it would be a `/`
+            match star {
+                Some((next, start)) if text.get(start).is_some_and(|ch| *ch != '/') => {
+                    star = Some((next, start + 1));
+                    (t, i) = (next, start + 1);
+                }
+                _ => match double_star {
+                    Some((next, start)) if start < text.len() => {
+                        star = None;
+                        double_star = Some((next, start + 1));
+                        (t, i) = (next, start + 1);
+                    }
+                    _ => return false,
+                },
+            }
+        }
+    }
+}
+impl Token {
+    /// true if this token matches `ch` on its own, false for `*` and `**`
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            Token::Char(c) => *c == ch,
+            Token::Any => ch != '/',
+            Token::Class { negated, ranges } => {
+                ch != '/' && ranges.iter().any(|(a, b)| *a <= ch && ch <= *b) != *negated
+            }
+            Token::Star | Token::DoubleStar => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Glob;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(is_match("*.rs", ".rs"));
+        assert!(!is_match("*.rs", "main.rs.bak"));
+        assert!(!is_match("*.rs", "src/main.rs"));
+        assert!(is_match("src/*.rs", "src/main.rs"));
+        assert!(is_match("**.rs", "src/bin/main.rs"));
+        assert!(is_match("src/**/mod.rs", "src/a/b/mod.rs"));
+        assert!(!is_match("src/**/mod.rs", "lib/a/mod.rs"));
+        assert!(is_match("a?c", "abc"));
+        assert!(!is_match("a?c", "ac"));
+        assert!(!is_match("a?c", "a/c"));
+        assert!(is_match("*", ""));
+        assert!(is_match("", ""));
+        assert!(!is_match("", "a"));
+        // the whole text has to match, and case matters
+        assert!(!is_match("main", "main.rs"));
+        assert!(!is_match("*.RS", "main.rs"));
+    }
+
+    #[test]
+    fn backtracking() {
+        assert!(is_match("*a*b*c", "xaxbxc"));
+        assert!(is_match("a*b*c", "abcbc"));
+        assert!(!is_match("*a*b", "ab/b"));
+        assert!(is_match("**/a*b", "x/y/a/aab"));
+        assert!(is_match("*/**/b", "x/y/z/b"));
+        assert!(!is_match("*/**/b", "x/b/c"));
+        // takes exponentially long if every star is tried with every length
+        let text = "a".repeat(200);
+        assert!(!is_match("*a*a*a*a*a*a*a*a*a*a*b", &text));
+        assert!(!is_match("**a**a**a**a**a**a**a**a**b", &text));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(is_match("[abc]", "b"));
+        assert!(!is_match("[abc]", "d"));
+        assert!(is_match("file[0-9].txt", "file7.txt"));
+        assert!(!is_match("file[0-9].txt", "filex.txt"));
+        assert!(is_match("[!0-9]", "x"));
+        assert!(is_match("[^0-9]", "x"));
+        assert!(!is_match("[!0-9]", "5"));
+        // a class never matches `/`, even when negated
+        assert!(!is_match("a[!x]b", "a/b"));
+        // `]` first and `-` last are literal
+        assert!(is_match("[]a]", "]"));
+        assert!(is_match("[a-]", "-"));
+        assert!(is_match("[a-]", "a"));
+        assert!(!is_match("[a-]", "b"));
+        assert!(is_match("[\\]]", "]"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(is_match("\\*", "*"));
+        assert!(!is_match("\\*", "a"));
+        assert!(is_match("a\\?", "a?"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Glob::new("a\\").is_err());
+        assert!(Glob::new("[ab").is_err());
+        assert!(Glob::new("[a-").is_err());
+        assert!(Glob::new("[").is_err());
+    }
+
+    #[test]
+    fn paths() {
+        assert!(Glob::new("src/*.rs").unwrap().matches_paths());
+        assert!(!Glob::new("*.rs").unwrap().matches_paths());
+        assert_eq!(Glob::new("*.rs").unwrap().as_str(), "*.rs");
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+
+use crate::{BackgroundTask, Share};
+
+/// files larger than this aren't searched, unless `content_search_max_size` is set in the config
+pub(crate) const DEFAULT_MAX_SIZE: u64 = 10 << 20;
+/// if one of the first this many bytes is 0, the file is considered binary and isn't searched
+const BINARY_CHECK_LEN: usize = 8192;
+/// at most this many matching lines per file are kept for the preview
+const MAX_PREVIEW_LINES: usize = 100;
+/// longer lines are cut off in the preview
+const MAX_PREVIEW_LINE_LEN: usize = 500;
+
+/// the matching lines of one file
+pub(crate) struct FileMatches {
+    /// the number of matching lines
+    pub count: usize,
+    /// line number and content of the first few matching lines
+    pub lines: Vec<(usize, String)>,
+}
+
+#[derive(Default)]
+pub(crate) struct ContentResults {
+    /// only contains files with at least one match
+    pub files: HashMap<PathBuf, FileMatches>,
+    /// set when results were added, reset when the list is filtered again
+    pub changed: bool,
+    /// true once all files were searched
+    pub done: bool,
+}
+
+/// A content search running in background threads.
+/// Dropping it stops the search.
+pub(crate) struct ContentSearch {
+    pattern: String,
+    paths: Arc<Vec<PathBuf>>,
+    cancel: Arc<AtomicBool>,
+    pub results: Arc<Mutex<ContentResults>>,
+}
+
+impl ContentSearch {
+    /// searches the contents of `paths` for lines matching `regex`.
+    /// files which aren't regular files, binary files, and files larger than `max_size` are skipped.
+    pub fn start(regex: Regex, paths: Vec<PathBuf>, max_size: u64, share: &mut Share) -> Self {
+        let paths = Arc::new(paths);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let results = Arc::new(Mutex::new(ContentResults::default()));
+        let search = Self {
+            pattern: regex.as_str().to_owned(),
+            paths: Arc::clone(&paths),
+            cancel: Arc::clone(&cancel),
+            results: Arc::clone(&results),
+        };
+        share.tasks.push(BackgroundTask::new(
+            format!("grep 0/{}", paths.len()),
+            move |status| {
+                let next = AtomicUsize::new(0);
+                let searched = AtomicUsize::new(0);
+                let threads = std::thread::available_parallelism().map_or(4, |v| v.get().min(8));
+                std::thread::scope(|scope| {
+                    for _ in 0..threads {
+                        scope.spawn(|| loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            if i >= paths.len() || cancel.load(Ordering::Relaxed) {
+                                break;
+                            }
+                            if let Some(matches) = search_file(&paths[i], &regex, max_size) {
+                                let mut results = results.lock().unwrap();
+                                results.files.insert(paths[i].clone(), matches);
+                                results.changed = true;
+                            }
+                            let searched = searched.fetch_add(1, Ordering::Relaxed) + 1;
+                            *status.lock().unwrap() = format!("grep {searched}/{}", paths.len());
+                        });
+                    }
+                });
+                let mut results = results.lock().unwrap();
+                results.done = true;
+                results.changed = true;
+                Ok(None)
+            },
+            false,
+        ));
+        search
+    }
+    /// false if the search has to be restarted because the pattern or the listed files changed
+    pub fn is_for(&self, regex: &Regex, paths: &[PathBuf]) -> bool {
+        self.pattern == regex.as_str() && self.paths.as_slice() == paths
+    }
+}
+impl Drop for ContentSearch {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// None if the file couldn't be read, was skipped, or contains no matches
+fn search_file(path: &Path, regex: &Regex, max_size: u64) -> Option<FileMatches> {
+    let mut file = fs::File::open(path).ok()?;
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() || metadata.len() > max_size {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(metadata.len() as usize);
+    // the file may have grown since the metadata was read
+    file.by_ref().take(max_size).read_to_end(&mut bytes).ok()?;
+    if bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0) {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    let mut matches = FileMatches {
+        count: 0,
+        lines: vec![],
+    };
+    for (i, line) in text.lines().enumerate() {
+        if regex.is_match(line) {
+            matches.count += 1;
+            if matches.lines.len() < MAX_PREVIEW_LINES {
+                matches
+                    .lines
+                    .push((i + 1, line.chars().take(MAX_PREVIEW_LINE_LEN).collect()));
+            }
+        }
+    }
+    (matches.count > 0).then_some(matches)
+}
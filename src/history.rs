@@ -0,0 +1,88 @@
+use std::{fs, path::PathBuf};
+
+/// history entries kept by default, unless `history_size` is set in the config
+pub(crate) const DEFAULT_SIZE: usize = 1000;
+
+/// Texts entered into the search bar, oldest first.
+/// Saved to `$XDG_STATE_HOME/tuifile/history` (or `~/.local/state/tuifile/history`), one entry per line.
+pub(crate) struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+    size: usize,
+}
+
+impl History {
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|v| !v.is_empty()) {
+            Some(PathBuf::from(dir).join("tuifile").join("history"))
+        } else {
+            std::env::var_os("HOME")
+                .filter(|v| !v.is_empty())
+                .map(|home| PathBuf::from(home).join(".local/state/tuifile/history"))
+        }
+    }
+    /// loads the history from `path`. if it can't be read, the history starts out empty.
+    pub fn load(path: Option<PathBuf>, size: usize) -> Self {
+        let mut entries: Vec<String> = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| {
+                text.lines()
+                    .filter(|line| !line.is_empty())
+                    .map(|line| line.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.drain(..entries.len().saturating_sub(size));
+        Self {
+            entries,
+            path,
+            size,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|v| v.as_str())
+    }
+    /// adds `text` as the newest entry, removing older entries with the same text, and saves the history.
+    pub fn add(&mut self, text: &str) {
+        if text.is_empty() || text.contains('\n') || self.size == 0 {
+            return;
+        }
+        if self.entries.last().is_some_and(|v| v == text) {
+            return;
+        }
+        self.entries.retain(|v| v != text);
+        self.entries.push(text.to_owned());
+        self.entries
+            .drain(..self.entries.len().saturating_sub(self.size));
+        // the history is only a convenience, failing to save it shouldn't interrupt anything
+        let _ = self.save();
+    }
+    fn save(&self) -> std::io::Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut text = self.entries.join("\n");
+            text.push('\n');
+            fs::write(path, text)?;
+        }
+        Ok(())
+    }
+    /// the index of the newest entry older than `before` (or of any entry, if `before` is None) which contains `query`
+    pub fn search(&self, query: &str, before: Option<usize>) -> Option<usize> {
+        self.entries[..before.unwrap_or(self.entries.len())]
+            .iter()
+            .rposition(|v| v.contains(query))
+    }
+}
+
+/// The state of a Ctrl+R search through the history
+pub(crate) struct HistorySearch {
+    pub query: String,
+    /// the history entry which was found, None if no entry contains `query`
+    pub found: Option<usize>,
+}
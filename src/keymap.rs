@@ -0,0 +1,418 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{Focus, TuiFile};
+
+/// Where a key binding can be used
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Context {
+    /// in all of the contexts below
+    Global,
+    Files,
+    /// choosing a directory in the path bar after `
+    PathBar,
+    /// entering a pattern after + or -
+    SelectBar,
+    SearchBar,
+    /// searching the history with Ctrl+R in the search bar or the select bar
+    HistorySearch,
+}
+
+impl Context {
+    /// the context of the keys pressed in `instance`
+    pub fn of(instance: &TuiFile) -> Self {
+        match instance.focus {
+            Focus::Files if instance.path_jump => Self::PathBar,
+            Focus::Files => Self::Files,
+            Focus::SelectBar { .. } if instance.history_search.is_some() => Self::HistorySearch,
+            Focus::SelectBar { .. } => Self::SelectBar,
+            Focus::SearchBar if instance.history_search.is_some() => Self::HistorySearch,
+            Focus::SearchBar => Self::SearchBar,
+        }
+    }
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Global => "Global",
+            Self::Files => "Files",
+            Self::PathBar => "Path Bar",
+            Self::SelectBar => "Select Bar",
+            Self::SearchBar => "Find/Filter Bar",
+            Self::HistorySearch => "History Search",
+        }
+    }
+}
+
+/// What a key binding does, see `TuiFile::key_action`
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Action {
+    // global
+    Quit,
+    PrevInstance,
+    NextInstance,
+    CloseInstance,
+    DuplicateInstance,
+    GoToTab,
+    MoveTabLeft,
+    MoveTabRight,
+    Help,
+    // files
+    Up,
+    Down,
+    Collapse,
+    Parent,
+    Enter,
+    ToggleAll,
+    ToggleCurrent,
+    DeselectAll,
+    SelectMatching,
+    DeselectMatching,
+    InvertSelection,
+    SelectExtension,
+    Mark,
+    Search,
+    Mode,
+    ToggleHidden,
+    NewDir,
+    Yank,
+    Cut,
+    Paste,
+    Inspect,
+    Remove,
+    Permissions,
+    Owner,
+    Depth,
+    TreeMode,
+    Shell,
+    Edit,
+    ViewMatches,
+    Preview,
+    PreviewWider,
+    PreviewNarrower,
+    SavedFilters,
+    Rescan,
+    Layout,
+    MessageLog,
+    PathJump,
+    // path bar
+    JumpToAncestor,
+    PathJumpCancel,
+    // select bar
+    SelectCancel,
+    SelectApply,
+    SelectBackspace,
+    SelectType,
+    // search bar
+    HistorySearch,
+    FilterPaths,
+    FilterAncestors,
+    PushFilter,
+    SearchCancel,
+    SearchApply,
+    NextSyntax,
+    PrevFilter,
+    NextFilter,
+    InvertFilter,
+    RemoveFilter,
+    HistoryPrev,
+    HistoryNext,
+    SearchBackspace,
+    SearchType,
+    // history search
+    HistoryUse,
+    HistoryCancel,
+    HistoryBackspace,
+    HistoryType,
+}
+
+/// One of the keys a binding reacts to
+#[derive(Clone, Copy)]
+pub(crate) enum Key {
+    Code(KeyCode),
+    /// any char in this range, like the digits
+    Range(char, char),
+    /// any char, with any modifiers. used for typing text.
+    Text,
+}
+
+pub(crate) struct Binding {
+    pub context: Context,
+    /// Ctrl or Alt, which have to be held. Shift is ignored, since it is needed to type chars like `+` on some keyboards.
+    modifiers: KeyModifiers,
+    keys: &'static [Key],
+    /// the binding is only used if this returns true, otherwise the next matching binding is
+    when: Option<fn(&TuiFile) -> bool>,
+    pub action: Action,
+    pub help: &'static str,
+}
+
+const fn bind(
+    context: Context,
+    modifiers: KeyModifiers,
+    keys: &'static [Key],
+    action: Action,
+    help: &'static str,
+) -> Binding {
+    Binding {
+        context,
+        modifiers,
+        keys,
+        when: None,
+        action,
+        help,
+    }
+}
+
+const fn bind_when(
+    when: fn(&TuiFile) -> bool,
+    context: Context,
+    keys: &'static [Key],
+    action: Action,
+    help: &'static str,
+) -> Binding {
+    Binding {
+        when: Some(when),
+        ..bind(context, NONE, keys, action, help)
+    }
+}
+
+const NONE: KeyModifiers = KeyModifiers::NONE;
+const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+const ALT: KeyModifiers = KeyModifiers::ALT;
+
+const fn ch(ch: char) -> Key {
+    Key::Code(KeyCode::Char(ch))
+}
+const UP: Key = Key::Code(KeyCode::Up);
+const DOWN: Key = Key::Code(KeyCode::Down);
+const LEFT: Key = Key::Code(KeyCode::Left);
+const RIGHT: Key = Key::Code(KeyCode::Right);
+const ENTER: Key = Key::Code(KeyCode::Enter);
+const ESC: Key = Key::Code(KeyCode::Esc);
+const BACKSPACE: Key = Key::Code(KeyCode::Backspace);
+
+/// in tree mode, Left/H on an expanded directory or an entry inside one doesn't leave the directory
+fn tree_entry(instance: &TuiFile) -> bool {
+    instance.tree_mode
+        && instance
+            .dir_content
+            .get(instance.current_index)
+            .is_some_and(|e| e.rel_depth > 0 || instance.expanded.contains(&e.path))
+}
+fn filter_selected(instance: &TuiFile) -> bool {
+    instance.selected_filter.is_some()
+}
+
+/// All key bindings. The first one matching a key is used, so more specific ones have to come first.
+/// Key presses are dispatched through this table, and the help (?) and `--help` are generated from it.
+#[rustfmt::skip]
+pub(crate) const KEYMAP: &[Binding] = {
+    use Action as A;
+    use Context::*;
+    &[
+        bind(Global, CTRL, &[ch('c'), ch('d')], A::Quit, "quit"),
+        bind(Global, CTRL, &[UP, ch('k')], A::PrevInstance, "go to the previous instance"),
+        bind(Global, CTRL, &[DOWN, ch('j')], A::NextInstance, "go to the next instance"),
+        bind(Global, CTRL, &[LEFT, ch('h')], A::CloseInstance, "close this instance"),
+        bind(Global, CTRL, &[RIGHT, ch('l')], A::DuplicateInstance, "duplicate this instance"),
+        bind(Global, ALT, &[Key::Range('0', '9')], A::GoToTab, "go to the instance with this number (see Tab Bar)"),
+        bind(Global, ALT, &[LEFT, ch('h')], A::MoveTabLeft, "move this instance's tab to the left"),
+        bind(Global, ALT, &[RIGHT, ch('l')], A::MoveTabRight, "move this instance's tab to the right"),
+        bind(Global, NONE, &[Key::Code(KeyCode::F(1))], A::Help, "show the keys which can be used here"),
+        bind(Files, NONE, &[UP, ch('k')], A::Up, "move the cursor up"),
+        bind(Files, NONE, &[DOWN, ch('j')], A::Down, "move the cursor down"),
+        bind_when(tree_entry, Files, &[LEFT, ch('h')], A::Collapse, "in tree mode: collapse the directory, or go to the directory the entry is in"),
+        bind(Files, NONE, &[LEFT, ch('h')], A::Parent, "go to the parent directory"),
+        bind(Files, NONE, &[RIGHT, ch('l')], A::Enter, "go into the directory (in tree mode: expand it, on a `... more not loaded` entry: load the next entries)"),
+        bind(Files, NONE, &[ch('a')], A::ToggleAll, "Alternate selection (toggle All)"),
+        bind(Files, NONE, &[ch('s')], A::ToggleCurrent, "Select or toggle current"),
+        bind(Files, NONE, &[ch('d')], A::DeselectAll, "Deselect all"),
+        bind(Files, NONE, &[ch('+')], A::SelectMatching, "select all entries matching a pattern (see Selecting)"),
+        bind(Files, NONE, &[ch('-')], A::DeselectMatching, "deselect all entries matching a pattern"),
+        bind(Files, NONE, &[ch('*')], A::InvertSelection, "invert the selection of all listed entries"),
+        bind(Files, NONE, &[ch('x')], A::SelectExtension, "select all files with the same eXtension as the current one"),
+        bind(Files, NONE, &[ch(' ')], A::Mark, "set a mark, then Space on another entry selects all visible entries between the two (Space on the marked entry removes the mark)"),
+        bind(Files, NONE, &[ch('f'), ch('/')], A::Search, "focus the Find/Filter bar"),
+        bind(Files, NONE, &[ch('m')], A::Mode, "set the Mode based on the find/filter bar text (see File List Modes)"),
+        bind(Files, NONE, &[ch('.')], A::ToggleHidden, "toggle hidden files (dotfiles)"),
+        bind(Files, NONE, &[ch('n')], A::NewDir, "New directory (name taken from the find/filter bar text)"),
+        bind(Files, NONE, &[ch('y')], A::Yank, "Yank (copy) the selected entries to the clipboard"),
+        bind(Files, NONE, &[ch('u')], A::Cut, "cUt the selected entries to the clipboard"),
+        bind(Files, NONE, &[ch('c')], A::Paste, "paste the clipboard to this directory (Copy, or move if the entries were cut)"),
+        bind(Files, NONE, &[ch('i')], A::Inspect, "Inspect the clipboard (see Clipboard)"),
+        bind(Files, NONE, &[ch('r')], A::Remove, "Remove the selected files and directories (not recursive: also requires selecting the directories' contents)"),
+        bind(Files, NONE, &[ch('p')], A::Permissions, "set Permissions (mode taken as base-8 number from the find/filter bar text)"),
+        bind(Files, NONE, &[ch('o')], A::Owner, "set Owner (and group - TODO!)"),
+        bind(Files, NONE, &[Key::Range('0', '9')], A::Depth, "set the recursive depth limit (1 = only this directory, 0 = infinite)"),
+        bind(Files, NONE, &[ch('t')], A::TreeMode, "toggle Tree mode (see Tree Mode)"),
+        bind(Files, NONE, &[ch('w')], A::Shell, "open a terminal ($SHELL) here"),
+        bind(Files, NONE, &[ch('e')], A::Edit, "open this entry in your editor ($EDITOR)"),
+        bind(Files, NONE, &[ch('v')], A::ViewMatches, "View the matching lines of this file (when filtering by content)"),
+        bind(Files, NONE, &[ch('z')], A::Preview, "toggle the preview pane (see Preview Pane)"),
+        bind(Files, NONE, &[ch('<')], A::PreviewWider, "make the preview pane wider"),
+        bind(Files, NONE, &[ch('>')], A::PreviewNarrower, "make the preview pane narrower"),
+        bind(Files, NONE, &[ch('b')], A::SavedFilters, "show the saved filters (Bookmarks, see Saved Filters)"),
+        bind(Files, NONE, &[ch('q')], A::Rescan, "Query files again in all instances (also clears the directory cache)"),
+        bind(Files, NONE, &[ch('g')], A::Layout, "change the layout (see Layout)"),
+        bind(Files, NONE, &[ch('!')], A::MessageLog, "show the message log (see Messages)"),
+        bind(Files, NONE, &[ch('`')], A::PathJump, "number the directories in the path bar, to go to one of them (see Path Bar)"),
+        bind(Files, NONE, &[ch('?')], A::Help, "show these keys"),
+        bind(PathBar, NONE, &[Key::Range('0', '9')], A::JumpToAncestor, "go to the directory with this number (1 = the parent directory, 0 = / or ~)"),
+        bind(PathBar, NONE, &[ESC, Key::Text], A::PathJumpCancel, "back"),
+        bind(SelectBar, NONE, &[ESC], A::SelectCancel, "back, without changing the selection"),
+        bind(SelectBar, NONE, &[ENTER], A::SelectApply, "back & (de)select all listed entries matching the pattern"),
+        bind(SelectBar, CTRL, &[ch('r')], A::HistorySearch, "search the history (see History Search)"),
+        bind(SelectBar, NONE, &[UP], A::HistoryPrev, "go back in the history of search texts"),
+        bind(SelectBar, NONE, &[DOWN], A::HistoryNext, "go forward in the history of search texts"),
+        bind(SelectBar, NONE, &[BACKSPACE], A::SelectBackspace, "delete"),
+        bind(SelectBar, NONE, &[Key::Text], A::SelectType, "type to enter the pattern"),
+        bind(SearchBar, CTRL, &[ch('r')], A::HistorySearch, "search the history (see History Search)"),
+        bind(SearchBar, CTRL, &[ch('p')], A::FilterPaths, "match the filter against Paths (relative to the current directory) instead of names"),
+        bind(SearchBar, CTRL, &[ch('t')], A::FilterAncestors, "also show the directories containing matches (dimmed), so the Tree still makes sense"),
+        bind(SearchBar, CTRL, &[ch('a')], A::PushFilter, "Add the filter to the filter stack and clear the search text"),
+        bind(SearchBar, NONE, &[ESC], A::SearchCancel, "back & discard"),
+        bind(SearchBar, NONE, &[ENTER], A::SearchApply, "back & filter"),
+        bind(SearchBar, NONE, &[Key::Code(KeyCode::Tab)], A::NextSyntax, "change the filter syntax (regex, glob, fuzzy, substring, query, content)"),
+        bind(SearchBar, NONE, &[LEFT], A::PrevFilter, "select the previous filter on the stack"),
+        bind(SearchBar, NONE, &[RIGHT], A::NextFilter, "select the next filter on the stack"),
+        bind_when(filter_selected, SearchBar, &[ch('!')], A::InvertFilter, "invert the selected filter"),
+        bind_when(filter_selected, SearchBar, &[BACKSPACE, Key::Code(KeyCode::Delete)], A::RemoveFilter, "remove the selected filter from the stack"),
+        bind(SearchBar, NONE, &[UP], A::HistoryPrev, "go back in the history of search texts"),
+        bind(SearchBar, NONE, &[DOWN], A::HistoryNext, "go forward in the history of search texts"),
+        bind(SearchBar, NONE, &[BACKSPACE], A::SearchBackspace, "delete"),
+        bind(SearchBar, NONE, &[Key::Text], A::SearchType, "type to enter the search regex (or glob, ...)"),
+        bind(HistorySearch, CTRL, &[ch('r')], A::HistorySearch, "find an older entry containing the text"),
+        bind(HistorySearch, NONE, &[ENTER], A::HistoryUse, "use the entry which was found"),
+        bind(HistorySearch, NONE, &[ESC], A::HistoryCancel, "go back to the text you typed"),
+        bind(HistorySearch, NONE, &[BACKSPACE], A::HistoryBackspace, "delete"),
+        bind(HistorySearch, NONE, &[Key::Text], A::HistoryType, "type to find the newest entry containing the text"),
+    ]
+};
+
+impl Key {
+    fn matches(&self, code: KeyCode) -> bool {
+        match (self, code) {
+            (Self::Code(key), code) => *key == code,
+            (Self::Range(start, end), KeyCode::Char(ch)) => (*start..=*end).contains(&ch),
+            (Self::Text, KeyCode::Char(_)) => true,
+            _ => false,
+        }
+    }
+    fn name(&self) -> String {
+        match self {
+            Self::Code(KeyCode::Char(' ')) => "Space".to_owned(),
+            Self::Code(KeyCode::Char(ch)) => ch.to_uppercase().to_string(),
+            Self::Code(KeyCode::F(n)) => format!("F{n}"),
+            Self::Code(code) => format!("{code:?}"),
+            Self::Range(start, end) => format!("{start}-{end}"),
+            Self::Text => "chars".to_owned(),
+        }
+    }
+}
+
+impl Binding {
+    fn matches(&self, e: &KeyEvent, instance: &TuiFile) -> bool {
+        self.keys.iter().any(|key| match key {
+            Key::Text => key.matches(e.code),
+            _ => e.modifiers - KeyModifiers::SHIFT == self.modifiers && key.matches(e.code),
+        }) && self.when.is_none_or(|when| when(instance))
+    }
+    /// the keys as they are shown in the help, like `Ctrl+Up/K`
+    pub fn keys(&self) -> String {
+        let mut keys = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            keys.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            keys.push_str("Alt+");
+        }
+        let names: Vec<String> = self.keys.iter().map(Key::name).collect();
+        // `F//` would be hard to read
+        if names.iter().any(|name| name == "/") {
+            keys.push_str(&names.join(" or "));
+        } else {
+            keys.push_str(&names.join("/"));
+        }
+        keys
+    }
+}
+
+/// the binding used for the key `e` pressed in `instance`
+pub(crate) fn find(e: &KeyEvent, instance: &TuiFile) -> Option<&'static Binding> {
+    let context = Context::of(instance);
+    KEYMAP.iter().find(|binding| {
+        (binding.context == context || binding.context == Context::Global)
+            && binding.matches(e, instance)
+    })
+}
+
+/// The state of the list of keys, while it is shown
+pub(crate) struct HelpView {
+    /// the keys of this context are shown first, followed by the global ones
+    pub context: Context,
+    /// only keys whose names or descriptions contain this text (ignoring case) are shown
+    pub filter: String,
+    /// the number of lines scrolled down
+    pub scroll: usize,
+}
+
+impl HelpView {
+    /// the lines shown below the title
+    pub fn lines(&self) -> Vec<String> {
+        let filter = self.filter.to_lowercase();
+        let mut lines = vec![];
+        for context in [self.context, Context::Global] {
+            let bindings: Vec<(String, &str)> = KEYMAP
+                .iter()
+                .filter(|binding| binding.context == context)
+                .map(|binding| (binding.keys(), binding.help))
+                .filter(|(keys, help)| {
+                    keys.to_lowercase().contains(&filter) || help.to_lowercase().contains(&filter)
+                })
+                .collect();
+            if bindings.is_empty() {
+                continue;
+            }
+            let keys_width = bindings
+                .iter()
+                .map(|(keys, _)| keys.len())
+                .max()
+                .unwrap_or(0);
+            lines.push(format!("{}:", context.title()));
+            for (keys, help) in bindings {
+                lines.push(format!("  {keys:keys_width$}  {help}"));
+            }
+        }
+        lines
+    }
+}
+
+/// all key bindings, listed under a heading for each context.
+/// with `markdown`, the headings start with `###` and keys like `+` are escaped, so they don't start a nested list (or a code span).
+pub(crate) fn keys_text(markdown: bool) -> String {
+    let mut text = String::new();
+    for context in [
+        Context::Global,
+        Context::Files,
+        Context::PathBar,
+        Context::SelectBar,
+        Context::SearchBar,
+        Context::HistorySearch,
+    ] {
+        if markdown {
+            text.push_str(&format!("### {}\n\n", context.title()));
+        } else {
+            text.push_str(&format!("{}:\n", context.title()));
+        }
+        for binding in KEYMAP.iter().filter(|binding| binding.context == context) {
+            let keys = binding.keys();
+            let escape = if markdown && keys.starts_with(['+', '-', '*', '>', '`']) {
+                "\\"
+            } else {
+                ""
+            };
+            text.push_str(&format!("- {escape}{keys} -> {}\n", binding.help));
+        }
+        text.push('\n');
+    }
+    text.pop();
+    text
+}
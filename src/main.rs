@@ -1,24 +1,69 @@
+mod cache;
+mod clipboard;
+mod config;
+mod filter;
+mod glob;
+mod grep;
+mod history;
+mod keymap;
+mod messages;
+mod path_bar;
+mod preview;
+mod query;
 mod run;
+mod saved;
+mod scan;
+mod tabs;
 mod tasks;
+mod theme;
 mod updates;
+mod width;
 
 use std::{
+    collections::HashSet,
     fs::{self, Metadata},
     io::{self, StdoutLock},
     path::PathBuf,
     sync::{Arc, Mutex},
     thread::JoinHandle,
+    time::Instant,
 };
 
-use clap::{command, Parser};
-use crossterm::terminal;
-use regex::Regex;
+use cache::SharedScanCache;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use clipboard::Clipboard;
+use config::Config;
+use crossterm::style::{self, Stylize};
+use crossterm::{cursor, queue, terminal};
+use filter::{FilterError, FilterSyntax, SearchFilter};
+use glob::Glob;
+use grep::ContentSearch;
+use history::{History, HistorySearch};
+use keymap::HelpView;
+use messages::Messages;
+use path_bar::PathBar;
+use preview::Preview;
+use saved::{SavedFilters, SavedPicker};
+use scan::SkipReason;
+use tabs::TabBar;
+use theme::Theme;
 use updates::Updates;
 
 const EXIT_NO_ABSOLUTE_PATH: i32 = 1;
+const EXIT_BAD_CONFIG: i32 = 2;
+const DEFAULT_ENTRY_LIMIT: usize = 10000;
 
 fn main() -> io::Result<()> {
-    let args = Args::parse();
+    // the keys are generated from the keymap, so they can't be in the doc comment
+    let command = Args::command().after_long_help(keymap::keys_text(false));
+    let args = match Args::from_arg_matches(&command.get_matches()) {
+        Ok(v) => v,
+        Err(e) => e.exit(),
+    };
+    if args.keys {
+        println!("{}", keymap::keys_text(true));
+        return Ok(());
+    }
     let current_dir = match args.dir {
         Some(dir) => {
             if args.dir_relative || dir.is_absolute() {
@@ -35,24 +80,120 @@ fn main() -> io::Result<()> {
         }
         None => std::env::current_dir().unwrap_or(PathBuf::from("/")),
     };
+    let config_path = args.config.or_else(Config::default_path);
+    let config = match Config::load(config_path.as_ref()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error in config file: {e}");
+            std::process::exit(EXIT_BAD_CONFIG);
+        }
+    };
+    let exclude = match config
+        .exclude
+        .iter()
+        .chain(args.exclude.iter())
+        .map(|v| Glob::new(v))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Invalid exclude pattern {e}");
+            std::process::exit(EXIT_BAD_CONFIG);
+        }
+    };
+    let saved_filters = match SavedFilters::load(SavedFilters::default_path()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error in saved filters: {e}");
+            std::process::exit(EXIT_BAD_CONFIG);
+        }
+    };
+    let mut theme = Theme::default();
+    if config.ls_colors.unwrap_or(true) {
+        if let Ok(ls_colors) = std::env::var("LS_COLORS") {
+            theme.apply_ls_colors(&ls_colors);
+        }
+    }
+    // themes are read from the `themes` directory next to the config file
+    let theme_path = config.theme.as_ref().and_then(|name| {
+        config_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(|dir| dir.join("themes").join(name))
+    });
+    if let Some(path) = &theme_path {
+        if let Err(e) = theme.load(path) {
+            eprintln!("Error in theme: {e}");
+            std::process::exit(EXIT_BAD_CONFIG);
+        }
+    }
+    let show_hidden = !args.no_hidden && config.show_hidden.unwrap_or(true);
+    let filter_syntax = config.filter_syntax.unwrap_or_default();
+    let content_search_max_size = config
+        .content_search_max_size
+        .unwrap_or(grep::DEFAULT_MAX_SIZE);
+    let one_file_system = args.one_file_system || config.one_file_system.unwrap_or(false);
+    let entry_limit = args
+        .entry_limit
+        .or(config.entry_limit)
+        .unwrap_or(DEFAULT_ENTRY_LIMIT);
+    let term_size = terminal::size()?;
     let mut share = Share {
         status: String::new(),
         tasks: vec![],
         active_instance: 0,
         total_instances: 1,
         stdout: io::stdout().lock(),
-        size: terminal::size()?,
+        size: term_size,
+        origin: (0, 0),
+        pane: 0,
+        term_size,
+        layout: config.layout.unwrap_or_default(),
+        tab_bar: config.tab_bar.unwrap_or(true).then(TabBar::default),
         shell_command: std::env::var("SHELL").unwrap_or("sh".to_string()),
         editor_command: std::env::var("EDITOR").unwrap_or("nano".to_string()),
         live_search: !args.no_live_search,
         info_what: vec![0, 1],
+        exclude,
+        one_file_system,
+        entry_limit,
+        scan_cache: Default::default(),
+        show_hidden,
+        filter_syntax,
+        content_search_max_size,
+        filter_paths: config.filter_paths.unwrap_or(false),
+        filter_ancestors: config.filter_ancestors.unwrap_or(false),
+        history: History::load(
+            History::default_path(),
+            config.history_size.unwrap_or(history::DEFAULT_SIZE),
+        ),
+        saved_filters,
+        clipboard: None,
+        show_preview: config.preview.unwrap_or(false),
+        preview_width: config.preview_width.unwrap_or(preview::DEFAULT_WIDTH),
+        theme,
+        home: std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .filter(|home| home.is_absolute() && home.parent().is_some()),
+        messages: Messages::default(),
     };
     if args.check {
         eprintln!("Shell: {}", share.shell_command);
         eprintln!("Editor: {}", share.editor_command);
+        match &config_path {
+            Some(path) => eprintln!("Config: {}", path.display()),
+            None => eprintln!("Config: none"),
+        }
+        match &theme_path {
+            Some(path) => eprintln!("Theme: {}", path.display()),
+            None => eprintln!("Theme: default"),
+        }
+        for glob in &share.exclude {
+            eprintln!("Exclude: {}", glob.as_str());
+        }
         return Ok(());
     }
-    let mut instances = vec![TuiFile::new(current_dir)?];
+    let mut instances = vec![TuiFile::new(current_dir, &share)?];
     TuiFile::term_setup_no_redraw(&mut share)?;
     let mut redraw = true;
     loop {
@@ -63,15 +204,30 @@ fn main() -> io::Result<()> {
             share.active_instance = instances.len() - 1;
         }
         share.total_instances = instances.len();
-        let instance = &mut instances[share.active_instance];
+        // the tab bar may have been cleared or changed, and doesn't take long to draw
+        if let Some(tab_bar) = &mut share.tab_bar {
+            tab_bar.invalidate();
+        }
         if redraw {
-            instance.updates.request_clear();
-            instance.updates.request_redraw();
-            if instance.active {
-                share.status = format!("{}", share.active_instance);
+            if share.layout == Layout::Single {
+                let instance = &mut instances[share.active_instance];
+                instance.updates.request_clear();
+                instance.updates.request_redraw();
+            } else {
+                share.draw_separators()?;
+                for instance in &mut instances {
+                    instance.updates.request_clear();
+                    instance.updates.request_redraw();
+                }
             }
         }
-        let cmd = instance.run(&mut share)?;
+        share.set_pane(share.active_instance);
+        let (before, rest) = instances.split_at_mut(share.active_instance);
+        let (instance, after) = match rest.split_first_mut() {
+            Some(v) => v,
+            None => break,
+        };
+        let cmd = instance.run(&mut share, before, after)?;
         redraw = match cmd {
             AppCmd::Quit => break,
             AppCmd::CloseInstance => {
@@ -93,41 +249,39 @@ fn main() -> io::Result<()> {
                 }
                 true
             }
+            AppCmd::FocusInstance(index) => {
+                if index < instances.len() {
+                    share.active_instance = index;
+                }
+                true
+            }
+            AppCmd::MoveInstanceLeft => {
+                if share.active_instance > 0 {
+                    instances.swap(share.active_instance - 1, share.active_instance);
+                    share.active_instance -= 1;
+                }
+                true
+            }
+            AppCmd::MoveInstanceRight => {
+                if share.active_instance + 1 < instances.len() {
+                    instances.swap(share.active_instance, share.active_instance + 1);
+                    share.active_instance += 1;
+                }
+                true
+            }
             AppCmd::AddInstance(new) => {
                 share.active_instance += 1;
-                instances.insert(share.active_instance, new);
+                instances.insert(share.active_instance, *new);
                 true
             }
-            AppCmd::CopyTo(destination) => {
-                instance.updates.request_redraw_infobar();
-                let src = instances
-                    .iter()
-                    .filter(|v| v.active)
-                    .map(|v| {
-                        (
-                            v.current_dir.clone(),
-                            v.dir_content
-                                .iter()
-                                .filter(|e| e.selected)
-                                .filter_map(|e| {
-                                    Some((
-                                        e.path.strip_prefix(&v.current_dir).ok()?.to_owned(),
-                                        e.rel_depth == v.scan_files_max_depth,
-                                    ))
-                                })
-                                .collect(),
-                        )
-                    })
-                    .collect();
-                tasks::task_copy(src, destination, &mut share);
-                false
-            }
             AppCmd::RescanFiles => {
                 for i in &mut instances {
                     i.updates.request_rescan_files();
                 }
-                false
+                // the other panes have to be drawn again after returning from a shell
+                share.layout != Layout::Single
             }
+            AppCmd::Redraw => true,
         };
     }
     TuiFile::term_reset(&mut share)?;
@@ -136,34 +290,20 @@ fn main() -> io::Result<()> {
 
 /// TUI file explorer. Long Help is available with --help.
 ///
-/// Controls:
-/// - Ctrl+Up/K => previous
-/// - Ctrl+Down/J => next
-/// - Ctrl+Left/H => close
-/// - Ctrl+Right/L => duplicate
-/// Files:
-/// - Up/K or Down/J => move selection
-/// - Left/H => go to parent directory
-/// - Right/L => go into selected entry
-/// - A => Alternate selection (toggle All)
-/// - S => Select or toggle current
-/// - D => Deselect all
-/// - F or / => focus Find/Filter bar
-/// - M => set Mode based on Find/Filter bar ((t/b)[seconds])
-/// - N => New directory from search text
-/// - C => Copy selected files to this directory.
-/// - R => Remove selected files and directories non-recursively
-/// - P -> set Permissions (mode taken as base-8 number from find/filter bar text)
-/// - O -> set Owner (and group - TODO!)
-/// - 1-9 or 0 => set recursive depth limit (0 = infinite)
-/// - Q => query files again if they have changes
-/// - W => open terminal here ($SHELL)
-/// - E => open in editor ($EDITOR <file/dir>)
-/// Find/Filter Bar:
-/// - Esc: back and discard
-/// - Enter: back and apply
-/// - Backspace: delete
-/// - type to enter search regex
+/// Press ? (or F1 while typing in a bar) to see the keys, which are also listed at the end of this help.
+///
+/// Mouse:
+/// - click => move the cursor, double click => enter the directory
+/// - Ctrl+click => select or deselect, Shift+click => select everything between the cursor and the clicked entry
+/// - wheel or dragging the scrollbar => scroll
+/// - click into another instance's pane or on its tab => make it the active instance
+/// - click on a directory in the path bar => go there
+///
+/// Filters:
+/// - type to enter search regex (or glob, ...). prefixes r: g: f: s: q: c: select the syntax.
+///   queries filter by metadata, like: size>100M and mtime<7d and not owner=root
+///   content searches show files whose contents match the regex
+///   a leading ! inverts the filter, like: !\.bak$
 #[derive(Parser, Debug)]
 #[command(version, verbatim_doc_comment)]
 struct Args {
@@ -180,6 +320,29 @@ struct Args {
     /// disables live search, only filtering the file list when enter is pressed.
     #[arg(long)]
     no_live_search: bool,
+    /// hides dotfiles when tuifile starts. they can be shown again with '.'.
+    #[arg(long)]
+    no_hidden: bool,
+    /// excludes entries matching this glob (and their contents) from all file lists.
+    /// globs containing a '/' are matched against the path relative to the listed directory.
+    /// can be used multiple times and adds to 'exclude' from the config file.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// when listing recursively, don't descend into directories on other file systems
+    /// (like /proc or network mounts).
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+    /// lists at most this many entries per directory, the rest can be loaded later
+    /// by pressing Right on the 'more not loaded' entry. 0 means no limit.
+    /// defaults to 10000.
+    #[arg(long, value_name = "N")]
+    entry_limit: Option<usize>,
+    /// the config file to use instead of $XDG_CONFIG_HOME/tuifile/config.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// prints the keys as markdown, like in the README, and exits.
+    #[arg(long)]
+    keys: bool,
 }
 
 struct Share {
@@ -187,7 +350,17 @@ struct Share {
     tasks: Vec<BackgroundTask>,
     active_instance: usize,
     total_instances: usize,
+    /// the size of the pane the instance draws into (see `set_pane`)
     size: (u16, u16),
+    /// the top left corner of the pane
+    origin: (u16, u16),
+    /// the index of the instance the pane belongs to
+    pane: usize,
+    /// the size of the terminal
+    term_size: (u16, u16),
+    layout: Layout,
+    /// None if the tab bar is turned off
+    tab_bar: Option<TabBar>,
     stdout: StdoutLock<'static>,
     //
     live_search: bool,
@@ -196,25 +369,130 @@ struct Share {
     /// 0: size
     /// 1: mode (permissions)
     info_what: Vec<u32>,
+    /// entries matching any of these aren't listed (see `ScanOptions::exclude`)
+    exclude: Vec<Glob>,
+    /// don't scan directories on other file systems (see `ScanOptions::one_file_system`)
+    one_file_system: bool,
+    /// max. number of entries listed per directory (see `ScanOptions::entry_limit`)
+    entry_limit: usize,
+    /// directory listings, shared by all instances and background scans
+    scan_cache: SharedScanCache,
+    /// initial value of `TuiFile::show_hidden`
+    show_hidden: bool,
+    /// initial value of `TuiFile::filter_syntax`
+    filter_syntax: FilterSyntax,
+    /// larger files are skipped by content searches
+    content_search_max_size: u64,
+    /// initial value of `TuiFile::filter_paths`
+    filter_paths: bool,
+    /// initial value of `TuiFile::filter_ancestors`
+    filter_ancestors: bool,
+    /// texts entered into the search bar, shared by all instances
+    history: History,
+    /// filters saved with B, shared by all instances
+    saved_filters: SavedFilters,
+    /// entries yanked or cut in any instance
+    clipboard: Option<Clipboard>,
+    /// initial value of `TuiFile::show_preview`
+    show_preview: bool,
+    /// the width of the preview pane, in percent of the terminal's width
+    preview_width: u16,
+    /// colors of the file list and info bar
+    theme: Theme,
+    /// $HOME, shown as `~` in the path bar
+    home: Option<PathBuf>,
+    /// what commands and background tasks reported, shown in the message line of the active instance
+    messages: Messages,
 }
 impl Share {
-    /// returns Some if any task has finished.
+    /// sets `size`, `origin` and `status` for drawing the instance at `index`.
+    /// side by side panes are separated by a column, see `draw_separators`.
+    /// the panes start below the tab bar.
+    fn set_pane(&mut self, index: usize) {
+        let top = self.panes_top();
+        let (width, height) = (self.term_size.0, self.term_size.1.saturating_sub(top));
+        let count = self.total_instances.max(1);
+        let (origin, size) = match self.layout {
+            Layout::Single => ((0, top), (width, height)),
+            Layout::Columns => {
+                let start = (index * (width as usize + 1) / count) as u16;
+                let end = ((index + 1) * (width as usize + 1) / count) as u16 - 1;
+                ((start, top), (end.saturating_sub(start), height))
+            }
+            Layout::Rows => {
+                let start = (index * height as usize / count) as u16;
+                let end = ((index + 1) * height as usize / count) as u16;
+                ((0, top + start), (width, end - start))
+            }
+        };
+        self.origin = origin;
+        self.size = size;
+        self.pane = index;
+        self.status = format!("{index}");
+    }
+    /// the row the panes start at, below the tab bar
+    fn panes_top(&self) -> u16 {
+        if self.tab_bar.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+    /// the index of the instance whose pane contains the cell at `x`, `y`
+    fn pane_at(&mut self, x: u16, y: u16) -> Option<usize> {
+        let current = self.pane;
+        let found = (0..self.total_instances).find(|i| {
+            self.set_pane(*i);
+            (self.origin.0..self.origin.0 + self.size.0).contains(&x)
+                && (self.origin.1..self.origin.1 + self.size.1).contains(&y)
+        });
+        self.set_pane(current);
+        found
+    }
+    /// clears the terminal and draws the lines between side by side panes
+    fn draw_separators(&mut self) -> io::Result<()> {
+        queue!(self.stdout, terminal::Clear(terminal::ClearType::All))?;
+        if self.layout == Layout::Columns {
+            let current = self.pane;
+            for i in 0..self.total_instances.saturating_sub(1) {
+                self.set_pane(i);
+                let x = self.origin.0 + self.size.0;
+                for y in self.panes_top()..self.term_size.1 {
+                    queue!(
+                        self.stdout,
+                        cursor::MoveTo(x, y),
+                        style::PrintStyledContent("│".dark_grey())
+                    )?;
+                }
+            }
+            self.set_pane(current);
+        }
+        Ok(())
+    }
+    /// returns Some if any task has finished, and shows what it reported in the message line.
     /// returns Some(true) if at least one of these tasks may have altered files.
     /// (this should trigger a rescan)
     fn check_bgtasks(&mut self) -> Option<bool> {
         let mut finished = false;
         let mut rescan = false;
-        for i in self
-            .tasks
-            .iter()
-            .enumerate()
-            .filter(|v| v.1.thread.is_finished())
-            .map(|v| v.0)
-            .collect::<Vec<_>>()
-        {
+        let mut i = 0;
+        while i < self.tasks.len() {
+            if !self.tasks[i].thread.is_finished() {
+                i += 1;
+                continue;
+            }
             let task = self.tasks.remove(i);
             finished = true;
             rescan |= task.rescan_after;
+            match task.thread.join() {
+                Ok(Ok(Some(text))) => self.messages.info(text),
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) => self.messages.error(e),
+                Err(_) => self.messages.error(format!(
+                    "{} crashed",
+                    task.status.lock().unwrap_or_else(|e| e.into_inner())
+                )),
+            }
         }
         if finished {
             Some(rescan)
@@ -223,15 +501,17 @@ impl Share {
         }
     }
 }
+/// what a background task reports when it's done: Some text to show as info, or an error
+type TaskResult = Result<Option<String>, String>;
 struct BackgroundTask {
     status: Arc<Mutex<String>>,
-    thread: JoinHandle<Result<(), String>>,
+    thread: JoinHandle<TaskResult>,
     rescan_after: bool,
 }
 impl BackgroundTask {
     pub fn new(
         text: String,
-        func: impl FnOnce(Arc<Mutex<String>>) -> Result<(), String> + Send + 'static,
+        func: impl FnOnce(Arc<Mutex<String>>) -> TaskResult + Send + 'static,
         rescan_after: bool,
     ) -> Self {
         let status = Arc::new(Mutex::new(text));
@@ -248,25 +528,89 @@ struct TuiFile {
     current_dir: PathBuf,
     dir_content: Vec<DirContent>,
     dir_content_len: usize,
-    dir_content_builder_task: Option<Arc<Mutex<Option<Result<Vec<DirContent>, String>>>>>,
+    /// the width of each column of the info table, see `DirContent::load`
+    info_widths: Vec<usize>,
+    dir_content_builder_task: Option<DirContentBuilderTask>,
     scroll: usize,
     current_index: usize,
     focus: Focus,
     scan_files_max_depth: usize,
+    /// in tree mode, only directories in `expanded` are scanned and `scan_files_max_depth` is ignored.
+    tree_mode: bool,
+    /// directories expanded in tree mode. kept when rescanning or leaving tree mode.
+    expanded: HashSet<PathBuf>,
+    show_hidden: bool,
     files_status_is_special: bool,
     files_status: String,
     search_text: String,
-    search_regex: Option<Regex>,
+    /// how `search_text` is interpreted if it doesn't start with a prefix like `g:`
+    filter_syntax: FilterSyntax,
+    /// the last valid filter compiled from `search_text`
+    search_filter: Option<SearchFilter>,
+    /// filters added with Ctrl+A, all of them have to match (in addition to `search_filter`)
+    filter_stack: Vec<SearchFilter>,
+    /// the filter on the stack selected with Left/Right in the search bar
+    selected_filter: Option<usize>,
+    /// the history entry shown in the search bar while browsing with Up/Down
+    history_index: Option<usize>,
+    /// the text which was in the search bar before browsing the history
+    history_draft: String,
+    /// Some while searching the history with Ctrl+R
+    history_search: Option<HistorySearch>,
+    /// set while `search_text` is invalid, shown below the search bar
+    search_error: Option<FilterError>,
+    /// if true, the filter is matched against the path relative to `current_dir` instead of the name
+    filter_paths: bool,
+    /// if true, directories containing entries which match the filter are shown too
+    filter_ancestors: bool,
+    /// Some while filtering by content
+    content_search: Option<ContentSearch>,
+    /// Some while the matching lines of this file are shown instead of the file list
+    content_preview: Option<PathBuf>,
+    /// Some while the saved filters are shown instead of the file list
+    saved_picker: Option<SavedPicker>,
+    /// Some while the clipboard is shown instead of the file list, the index of the highlighted entry
+    clipboard_view: Option<usize>,
+    /// Some while the keys are shown instead of the file list
+    help: Option<HelpView>,
+    /// Some while the message log is shown instead of the file list, the number of lines scrolled down
+    message_log: Option<usize>,
+    /// true if the message line is drawn below the search bar, see `Messages`
+    message_line: bool,
+    /// the current directory in the info bar, where it was last drawn
+    path_bar: PathBar,
+    /// true after ` was pressed, while the directories in the path bar are numbered
+    path_jump: bool,
+    /// if true, the preview pane is shown next to the file list
+    show_preview: bool,
+    /// the preview of the entry under the cursor
+    preview: Option<Preview>,
+    /// the pattern entered after pressing + or -
+    select_text: String,
+    /// the number of selected entries and their total size, `None` until it is needed after the selection changed
+    selection_size: Option<(usize, u64)>,
+    /// the entry marked with Space, the start of the range which is selected when Space is pressed again
+    mark: Option<PathBuf>,
+    /// when and on which entry the file list was last clicked, to detect double clicks
+    last_click: Option<(Instant, usize)>,
+    /// true while the scrollbar is dragged with the mouse
+    dragging_scrollbar: bool,
+    /// true if `dir_content` is sorted by the filter's score rather than in the order it was listed in
+    ranked: bool,
     last_drawn_files_height: usize,
     last_drawn_files_count: usize,
     last_files_max_scroll: usize,
-    after_rescanning_files: Vec<Box<dyn FnOnce(&mut Self)>>,
+    after_rescanning_files: Vec<AfterRescanningFiles>,
     scan_files_mode: ScanFilesMode,
 }
-#[derive(Clone)]
+/// the result of a threaded scan, `Some` once the thread is done.
+type DirContentBuilderTask = Arc<Mutex<Option<Result<Vec<DirContent>, String>>>>;
+type AfterRescanningFiles = Box<dyn FnOnce(&mut TuiFile)>;
+#[derive(Clone, Default)]
 enum ScanFilesMode {
     /// file-scanning blocks the main thread.
     /// prevents flickering.
+    #[default]
     Blocking,
     /// file-scanning doesn't block the main thread.
     /// leads to flickering as the file list appears empty until the thread finishes.
@@ -280,24 +624,39 @@ enum ScanFilesMode {
     /// prevents flickering but will scan the first files twice if the timeout is reached.
     TimeoutThenThreaded(f32),
 }
-impl Default for ScanFilesMode {
-    fn default() -> Self {
-        Self::Blocking
-    }
-}
 #[derive(Clone)]
 struct DirContent {
     path: PathBuf,
     name: String,
-    name_charlen: usize,
+    /// the number of terminal columns the name takes up
+    name_width: usize,
     rel_depth: usize,
     passes_filter: bool,
     selected: bool,
     info: String,
+    /// the tree branch glyphs drawn before the name, set when filtering
+    tree: String,
     more: DirContentType,
+    /// set if this is a directory whose contents weren't listed
+    skipped: Option<SkipReason>,
+    /// the position in the list when it was last filtered without being sorted by score
+    order: usize,
+    /// which chars of the name matched the filter
+    highlight: Vec<usize>,
+    /// true if this directory doesn't match the filter, but is shown because entries inside it do
+    context: bool,
 }
 #[derive(Clone)]
 enum DirContentType {
+    /// The metadata hasn't been read yet (see `DirContent::load`)
+    Pending,
+    /// Not an actual entry, but a placeholder for the `remaining` entries
+    /// of the directory at `path` which weren't listed because of the entry limit.
+    /// The first `loaded` entries of that directory are already in the list.
+    More {
+        loaded: usize,
+        remaining: usize,
+    },
     /// Couldn't get more info on this entry
     Err(String),
     Dir {
@@ -308,21 +667,69 @@ enum DirContentType {
     },
     Symlink {
         metadata: Metadata,
+        /// the metadata of the symlink's target, None if it doesn't exist
+        target: Option<Metadata>,
     },
 }
 #[derive(Clone)]
 enum Focus {
     Files,
     SearchBar,
+    /// entering a pattern to (de)select all entries matching it
+    SelectBar {
+        deselect: bool,
+    },
+}
+/// How the instances are arranged on the screen
+#[derive(Clone, Copy, PartialEq, Default)]
+enum Layout {
+    /// only the active instance is shown
+    #[default]
+    Single,
+    /// all instances are shown side by side
+    Columns,
+    /// all instances are shown above each other
+    Rows,
+}
+impl Layout {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "single" => Some(Self::Single),
+            "columns" => Some(Self::Columns),
+            "rows" => Some(Self::Rows),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Single => "single",
+            Self::Columns => "columns",
+            Self::Rows => "rows",
+        }
+    }
+    pub fn next(self) -> Self {
+        match self {
+            Self::Single => Self::Columns,
+            Self::Columns => Self::Rows,
+            Self::Rows => Self::Single,
+        }
+    }
 }
 enum AppCmd {
     Quit,
     CloseInstance,
     NextInstance,
     PrevInstance,
-    AddInstance(TuiFile),
-    CopyTo(PathBuf),
+    /// make the instance with this index the active one
+    FocusInstance(usize),
+    /// swap the active instance with the one before it
+    MoveInstanceLeft,
+    /// swap the active instance with the one after it
+    MoveInstanceRight,
+    AddInstance(Box<TuiFile>),
     RescanFiles,
+    /// redraw all instances, like after changing the layout or the terminal's size
+    Redraw,
 }
 impl TuiFile {
     pub fn clone(&self) -> Self {
@@ -332,15 +739,46 @@ impl TuiFile {
             current_dir: self.current_dir.clone(),
             dir_content: self.dir_content.clone(),
             dir_content_len: self.dir_content_len,
+            info_widths: self.info_widths.clone(),
             dir_content_builder_task: None,
             scroll: self.scroll,
             current_index: self.current_index,
             focus: self.focus.clone(),
             scan_files_max_depth: self.scan_files_max_depth,
+            tree_mode: self.tree_mode,
+            expanded: self.expanded.clone(),
+            show_hidden: self.show_hidden,
             files_status_is_special: self.files_status_is_special,
             files_status: self.files_status.clone(),
             search_text: self.search_text.clone(),
-            search_regex: self.search_regex.clone(),
+            filter_syntax: self.filter_syntax,
+            search_filter: self.search_filter.clone(),
+            filter_stack: self.filter_stack.clone(),
+            selected_filter: None,
+            history_index: None,
+            history_draft: String::new(),
+            history_search: None,
+            search_error: self.search_error.clone(),
+            filter_paths: self.filter_paths,
+            filter_ancestors: self.filter_ancestors,
+            // started again when the new instance filters its files
+            content_search: None,
+            content_preview: None,
+            saved_picker: None,
+            clipboard_view: None,
+            help: None,
+            message_log: None,
+            message_line: false,
+            path_bar: PathBar::default(),
+            path_jump: false,
+            show_preview: self.show_preview,
+            preview: None,
+            select_text: String::new(),
+            mark: None,
+            selection_size: None,
+            last_click: None,
+            dragging_scrollbar: false,
+            ranked: self.ranked,
             last_drawn_files_height: self.last_drawn_files_height,
             last_drawn_files_count: self.last_drawn_files_count,
             last_files_max_scroll: self.last_files_max_scroll,
@@ -348,7 +786,7 @@ impl TuiFile {
             scan_files_mode: self.scan_files_mode.clone(),
         }
     }
-    pub fn new(current_dir: PathBuf) -> io::Result<Self> {
+    pub fn new(current_dir: PathBuf, share: &Share) -> io::Result<Self> {
         // state
         let (_width, _height) = terminal::size()?;
         let updates = u32::MAX;
@@ -358,15 +796,45 @@ impl TuiFile {
             current_dir,
             dir_content: vec![],
             dir_content_len: 0,
+            info_widths: vec![],
             dir_content_builder_task: None,
             scroll: 0,
             current_index: 0,
             focus: Focus::Files,
             scan_files_max_depth: 0,
+            tree_mode: false,
+            expanded: HashSet::new(),
+            show_hidden: share.show_hidden,
             files_status_is_special: false,
             files_status: String::new(),
             search_text: String::new(),
-            search_regex: None,
+            filter_syntax: share.filter_syntax,
+            search_filter: None,
+            filter_stack: vec![],
+            selected_filter: None,
+            history_index: None,
+            history_draft: String::new(),
+            history_search: None,
+            search_error: None,
+            filter_paths: share.filter_paths,
+            filter_ancestors: share.filter_ancestors,
+            content_search: None,
+            content_preview: None,
+            saved_picker: None,
+            clipboard_view: None,
+            help: None,
+            message_log: None,
+            message_line: false,
+            path_bar: PathBar::default(),
+            path_jump: false,
+            show_preview: share.show_preview,
+            preview: None,
+            select_text: String::new(),
+            mark: None,
+            selection_size: None,
+            last_click: None,
+            dragging_scrollbar: false,
+            ranked: false,
             last_drawn_files_height: 0,
             last_drawn_files_count: 0,
             last_files_max_scroll: 0,
@@ -433,6 +901,20 @@ impl TuiFile {
             e.name == name || e.name.ends_with('/') && e.name[..e.name.len() - 1] == name
         });
     }
+    /// if the entries were sorted by a ranked filter, puts them back in the order they were listed in,
+    /// so that the tree structure can be relied on again. the current entry stays the same.
+    /// the next time the files are filtered, they are sorted again.
+    fn restore_scan_order(&mut self) {
+        if self.ranked {
+            self.ranked = false;
+            let order = self.dir_content.get(self.current_index).map(|e| e.order);
+            self.dir_content.sort_by_key(|e| e.order);
+            if let Some(order) = order {
+                self.current_index = order;
+            }
+            self.updates.request_filter_files();
+        }
+    }
     fn request_rescan_files_then_select_current_again(&mut self) {
         if let Some(c) = self.dir_content.get(self.current_index) {
             self.request_rescan_files_then_select_by_name(c.name.clone());
@@ -0,0 +1,129 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crossterm::style::Color;
+
+/// at most this many messages are kept in the message log
+const LOG_SIZE: usize = 200;
+
+/// `1 entry` or `n entries`, for messages about what a command did
+pub(crate) fn entries(n: usize) -> String {
+    if n == 1 {
+        "1 entry".to_owned()
+    } else {
+        format!("{n} entries")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    /// how long a message is shown in the message line
+    fn duration(self) -> Duration {
+        match self {
+            Self::Info => Duration::from_secs(3),
+            Self::Warning => Duration::from_secs(5),
+            Self::Error => Duration::from_secs(10),
+        }
+    }
+    pub fn color(self) -> Color {
+        match self {
+            Self::Info => Color::Green,
+            Self::Warning => Color::Yellow,
+            Self::Error => Color::Red,
+        }
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+pub(crate) struct Message {
+    pub level: Level,
+    pub text: String,
+    pub time: Instant,
+}
+
+impl Message {
+    /// the line shown in the message log, like `12s ago  error: ...`
+    pub fn log_line(&self) -> String {
+        let secs = self.time.elapsed().as_secs();
+        let ago = match secs {
+            0..60 => format!("{secs}s"),
+            60..3600 => format!("{}m", secs / 60),
+            _ => format!("{}h", secs / 3600),
+        };
+        format!("{ago:>3} ago  {}: {}", self.level.label(), self.text)
+    }
+}
+
+/// What commands and background tasks report. The newest message is shown in the message line
+/// at the bottom of the active instance until it times out, all of them are in the message log (!).
+#[derive(Default)]
+pub(crate) struct Messages {
+    /// oldest first
+    pub log: VecDeque<Message>,
+    /// true while the newest message is shown in the message line
+    shown: bool,
+    /// true if the message line changed since `take_changed` was last called
+    changed: bool,
+}
+
+impl Messages {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Level::Info, text.into());
+    }
+    pub fn warning(&mut self, text: impl Into<String>) {
+        self.push(Level::Warning, text.into());
+    }
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Level::Error, text.into());
+    }
+    fn push(&mut self, level: Level, text: String) {
+        if self.log.len() >= LOG_SIZE {
+            self.log.pop_front();
+        }
+        self.log.push_back(Message {
+            level,
+            text,
+            time: Instant::now(),
+        });
+        self.shown = true;
+        self.changed = true;
+    }
+    /// hides the message line once its message timed out
+    pub fn expire(&mut self) {
+        if self
+            .current()
+            .is_some_and(|message| message.time.elapsed() >= message.level.duration())
+        {
+            self.dismiss();
+        }
+    }
+    /// hides the message line
+    pub fn dismiss(&mut self) {
+        if self.shown {
+            self.shown = false;
+            self.changed = true;
+        }
+    }
+    /// the message shown in the message line
+    pub fn current(&self) -> Option<&Message> {
+        self.log.back().filter(|_| self.shown)
+    }
+    /// true if the message line changed since this was last called
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+use crossterm::style::{Attribute, ContentStyle, StyledContent};
+
+use crate::width;
+
+/// replaces the directories left out of a path which doesn't fit
+const ELLIPSIS: &str = "…";
+
+/// One directory in the path bar
+struct Crumb {
+    /// the directory's name, `/` for the root directory, or `~` for the home directory
+    name: String,
+    path: PathBuf,
+}
+
+/// the directories from `/` (or `~`, if `dir` is in `home`) down to `dir`
+fn crumbs(dir: &Path, home: Option<&Path>) -> Vec<Crumb> {
+    let mut crumbs = vec![];
+    let mut path = PathBuf::new();
+    let rest = match home.and_then(|home| Some((home, dir.strip_prefix(home).ok()?))) {
+        Some((home, rest)) => {
+            path.push(home);
+            crumbs.push(Crumb {
+                name: "~".to_owned(),
+                path: path.clone(),
+            });
+            rest
+        }
+        None => dir,
+    };
+    for component in rest.components() {
+        path.push(component);
+        let name = match component {
+            Component::RootDir => "/".to_owned(),
+            c => width::escape_control(&c.as_os_str().to_string_lossy()).into_owned(),
+        };
+        crumbs.push(Crumb {
+            name,
+            path: path.clone(),
+        });
+    }
+    crumbs
+}
+
+/// the directory `number` in the path bar: 1 is the parent directory, 2 its parent and so on, 0 is `/` (or `~`)
+pub(crate) fn ancestor(dir: &Path, home: Option<&Path>, number: usize) -> Option<PathBuf> {
+    let mut crumbs = crumbs(dir, home);
+    let index = match number {
+        0 => 0,
+        _ => crumbs.len().checked_sub(number + 1)?,
+    };
+    // the current directory isn't an ancestor
+    if index + 1 >= crumbs.len() {
+        return None;
+    }
+    Some(crumbs.swap_remove(index).path)
+}
+
+/// The current directory in the info bar, split into the directories it is in.
+/// If it doesn't fit, the directories after `/` (or `~`) are replaced with `…`, starting with the outermost.
+/// Clicking a directory goes there, clicking `…` goes to the innermost directory it replaces.
+#[derive(Default)]
+pub(crate) struct PathBar {
+    /// the columns each drawn directory takes up, and its path
+    columns: Vec<(Range<u16>, PathBuf)>,
+}
+
+impl PathBar {
+    /// forgets where the directories were drawn, like when the path bar didn't fit
+    pub fn clear(&mut self) {
+        self.columns.clear();
+    }
+    /// the directory drawn at column `x`
+    pub fn path_at(&self, x: u16) -> Option<&Path> {
+        self.columns
+            .iter()
+            .find(|(columns, _)| columns.contains(&x))
+            .map(|(_, path)| path.as_path())
+    }
+    /// the parts of the path bar for `dir`, which is drawn into `columns`, and the number of columns they take up.
+    /// with `numbers`, the directories which can be jumped to are prefixed with their number (see `ancestor`).
+    pub fn layout(
+        &mut self,
+        dir: &Path,
+        home: Option<&Path>,
+        columns: Range<u16>,
+        numbers: bool,
+        style: ContentStyle,
+        home_style: ContentStyle,
+    ) -> (Vec<StyledContent<String>>, usize) {
+        self.columns.clear();
+        let (x, max_width) = (columns.start, columns.len());
+        let crumbs = crumbs(dir, home);
+        let last = match crumbs.len().checked_sub(1) {
+            Some(v) => v,
+            None => return (vec![], 0),
+        };
+        let mut label_style = ContentStyle::new();
+        label_style.attributes.set(Attribute::Reverse);
+        // the directories after the first one which are left out, starting with the outermost
+        let mut skip = 0;
+        let mut parts;
+        loop {
+            parts = vec![];
+            let mut previous: Option<usize> = None;
+            for i in (0..=last).filter(|i| *i == 0 || *i > skip) {
+                if let Some(previous) = previous {
+                    // there is no `/` after `/`
+                    if crumbs[previous].name != "/" {
+                        parts.push(("/".to_owned(), style, None));
+                    }
+                    if previous + 1 < i {
+                        parts.push((ELLIPSIS.to_owned(), style, Some(i - 1)));
+                        parts.push(("/".to_owned(), style, None));
+                    }
+                }
+                let number = match i {
+                    0 if last > 0 => Some(0),
+                    _ if i < last && last - i <= 9 => Some(last - i),
+                    _ => None,
+                };
+                if let Some(n) = number.filter(|_| numbers) {
+                    parts.push((n.to_string(), label_style, Some(i)));
+                }
+                let crumb_style = if i == 0 && crumbs[0].name == "~" {
+                    home_style
+                } else {
+                    style
+                };
+                parts.push((crumbs[i].name.clone(), crumb_style, Some(i)));
+                previous = Some(i);
+            }
+            let total: usize = parts.iter().map(|(text, _, _)| width::width(text)).sum();
+            if total <= max_width || skip + 1 >= last {
+                break;
+            }
+            skip += 1;
+        }
+        // if it still doesn't fit, the end of the current directory's name is shown
+        let total: usize = parts.iter().map(|(text, _, _)| width::width(text)).sum();
+        if total > max_width && parts.len() > 1 {
+            let (text, _, _) = parts.last_mut().unwrap();
+            let before = total - width::width(text);
+            *text = width::truncate_start(text, max_width.saturating_sub(before))
+                .0
+                .to_owned();
+        }
+        let mut styled = vec![];
+        let mut drawn = 0;
+        for (text, style, target) in parts {
+            if drawn >= max_width {
+                break;
+            }
+            let (text, text_width) = width::truncate(&text, max_width - drawn);
+            if let Some(target) = target {
+                let start = x + drawn as u16;
+                self.columns.push((
+                    start..start + text_width as u16,
+                    crumbs[target].path.clone(),
+                ));
+            }
+            styled.push(StyledContent::new(style, text.to_owned()));
+            drawn += text_width;
+        }
+        (styled, drawn)
+    }
+}
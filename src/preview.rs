@@ -0,0 +1,332 @@
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::SystemTime;
+
+use crossterm::style::Color;
+use syntect::easy::ScopeRangeIterator;
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+use unicode_width::UnicodeWidthChar;
+
+use crate::scan;
+
+/// the preview pane's width in percent of the terminal's width, unless `preview_width` is set in the config
+pub(crate) const DEFAULT_WIDTH: u16 = 40;
+/// at most this many bytes of a file are read
+const MAX_READ: u64 = 64 << 10;
+/// at most this many lines are shown
+const MAX_LINES: usize = 500;
+/// at most this many entries of a directory are read (and sorted)
+const MAX_DIR_ENTRIES: usize = 10_000;
+/// bytes per line in the hex dump of binary files
+const HEX_WIDTH: usize = 16;
+/// tabs in text files are expanded to the next multiple of this column
+const TAB_WIDTH: usize = 4;
+
+/// the grammars embedded in the binary, loaded the first time a text file is previewed
+static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+
+/// a part of a line and its color, None for the terminal's default color
+pub(crate) type Span = (String, Option<Color>);
+
+/// The preview of one entry, loaded by the preview thread.
+pub(crate) struct Preview {
+    pub path: PathBuf,
+    /// None until the thread is done
+    pub lines: Arc<Mutex<Option<Vec<Vec<Span>>>>>,
+    /// true once the loaded lines were drawn
+    pub drawn: bool,
+}
+
+/// what the preview thread should load, and where to put it
+type Request = (PathBuf, Weak<Mutex<Option<Vec<Vec<Span>>>>>);
+
+impl Preview {
+    pub fn start(path: PathBuf) -> Self {
+        let lines = Arc::new(Mutex::new(None));
+        let _ = worker().send((path.clone(), Arc::downgrade(&lines)));
+        Self {
+            path,
+            lines,
+            drawn: false,
+        }
+    }
+}
+
+/// the sender for the one thread which loads all previews, one at a time.
+/// if the cursor moves on before a preview is loaded, the `Preview` is dropped, and the
+/// thread skips the request (or stops loading it) because the `Weak` can't be upgraded anymore.
+fn worker() -> &'static Sender<Request> {
+    static WORKER: OnceLock<Sender<Request>> = OnceLock::new();
+    WORKER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Request>();
+        std::thread::spawn(move || {
+            for (path, lines) in receiver {
+                if lines.strong_count() == 0 {
+                    continue;
+                }
+                let loaded = load(&path, &|| lines.strong_count() == 0);
+                if let Some(lines) = lines.upgrade() {
+                    *lines.lock().unwrap() = Some(loaded);
+                }
+            }
+        });
+        sender
+    })
+}
+
+/// the first lines of text files, the contents of directories, a hex dump of binary files,
+/// and the metadata of anything else (or if the entry can't be read).
+/// `stale` returns true once nobody needs the preview anymore.
+fn load(path: &Path, stale: &dyn Fn() -> bool) -> Vec<Vec<Span>> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(v) => v,
+        Err(e) => return plain(vec![format!("error: {e}")]),
+    };
+    let file_type = metadata.file_type();
+    let result = if file_type.is_dir() {
+        load_dir(path, stale).map(plain)
+    } else if file_type.is_file() {
+        load_file(path)
+    } else {
+        Ok(vec![])
+    };
+    match result {
+        Ok(lines) if !lines.is_empty() => lines,
+        Ok(_) => plain(describe(path, &metadata)),
+        Err(e) => {
+            let mut lines = describe(path, &metadata);
+            lines.push(format!("error: {e}"));
+            plain(lines)
+        }
+    }
+}
+
+/// the directory's entries, sorted. huge directories are only read up to `MAX_DIR_ENTRIES`.
+fn load_dir(path: &Path, stale: &dyn Fn() -> bool) -> std::io::Result<Vec<String>> {
+    let mut names = vec![];
+    let mut capped = false;
+    for e in fs::read_dir(path)?.flatten() {
+        if names.len() == MAX_DIR_ENTRIES {
+            capped = true;
+            break;
+        }
+        if stale() {
+            return Ok(vec![]);
+        }
+        let mut name = e.file_name().to_string_lossy().into_owned();
+        if e.file_type().is_ok_and(|t| t.is_dir()) {
+            name.push('/');
+        }
+        names.push(name);
+    }
+    names.sort_unstable();
+    let mut lines = vec![if capped {
+        format!("more than {MAX_DIR_ENTRIES} entries, only the first ones read are shown")
+    } else {
+        format!(
+            "{} entr{}",
+            names.len(),
+            if names.len() == 1 { "y" } else { "ies" }
+        )
+    }];
+    lines.extend(names.into_iter().take(MAX_LINES));
+    Ok(lines)
+}
+
+fn load_file(path: &Path) -> std::io::Result<Vec<Vec<Span>>> {
+    let mut bytes = vec![];
+    fs::File::open(path)?
+        .take(MAX_READ)
+        .read_to_end(&mut bytes)?;
+    if bytes.contains(&0) {
+        return Ok(plain(
+            bytes
+                .chunks(HEX_WIDTH)
+                .take(MAX_LINES)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+                    let ascii: String = chunk
+                        .iter()
+                        .map(|b| {
+                            if b.is_ascii_graphic() || *b == b' ' {
+                                *b as char
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect();
+                    format!(
+                        "{:08x}  {:<width$}  {ascii}",
+                        i * HEX_WIDTH,
+                        hex.join(" "),
+                        width = HEX_WIDTH * 3 - 1
+                    )
+                })
+                .collect(),
+        ));
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    // the grammars expect each line to end with its newline
+    let lines: Vec<&str> = text.split_inclusive('\n').take(MAX_LINES).collect();
+    Ok(highlight(path, &lines))
+}
+
+/// the lines with line numbers, colored by the grammar for the file's extension, name or shebang line
+fn highlight(path: &Path, lines: &[&str]) -> Vec<Vec<Span>> {
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let syntax = [path.extension(), path.file_name()]
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.to_str())
+        .find_map(|v| syntaxes.find_syntax_by_extension(v))
+        .or_else(|| {
+            lines
+                .first()
+                .and_then(|v| syntaxes.find_syntax_by_first_line(v))
+        });
+    let mut state = syntax.map(|v| (ParseState::new(v), ScopeStack::new()));
+    let number_width = lines.len().to_string().len();
+    let mut result = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let mut spans = vec![(format!("{:>number_width$} ", i + 1), Some(Color::DarkGrey))];
+        let mut column = 0;
+        let ops = match &mut state {
+            Some((parse_state, _)) => parse_state.parse_line(line, syntaxes).ok(),
+            None => None,
+        };
+        match (&mut state, ops) {
+            (Some((_, stack)), Some(ops)) => {
+                for (range, op) in ScopeRangeIterator::new(&ops, line) {
+                    let _ = stack.apply(op);
+                    push_text(&mut spans, &mut column, &line[range], scope_color(stack));
+                }
+            }
+            _ => {
+                // without a grammar, or if parsing failed, the rest of the file isn't colored
+                state = None;
+                push_text(&mut spans, &mut column, line, None);
+            }
+        }
+        result.push(spans);
+    }
+    result
+}
+
+/// the terminal's palette color for the innermost scope which has one
+fn scope_color(stack: &ScopeStack) -> Option<Color> {
+    stack.as_slice().iter().rev().find_map(|scope| {
+        let name = scope.build_string();
+        let color = if name.starts_with("comment") {
+            Color::DarkGrey
+        } else if name.starts_with("string") || name.starts_with("markup.inserted") {
+            Color::Green
+        } else if name.starts_with("constant") || name.starts_with("entity.other.attribute-name") {
+            Color::Yellow
+        } else if name.starts_with("keyword") || name.starts_with("storage.modifier") {
+            Color::Magenta
+        } else if name.starts_with("storage")
+            || name.starts_with("support.type")
+            || name.starts_with("entity.name.type")
+            || name.starts_with("entity.name.class")
+        {
+            Color::Cyan
+        } else if name.starts_with("entity.name.function")
+            || name.starts_with("support.function")
+            || name.starts_with("markup.heading")
+        {
+            Color::Blue
+        } else if name.starts_with("entity.name.tag")
+            || name.starts_with("invalid")
+            || name.starts_with("markup.deleted")
+        {
+            Color::Red
+        } else {
+            return None;
+        };
+        Some(color)
+    })
+}
+
+/// appends `text` to the line, expanding tabs and leaving out other control characters
+fn push_text(spans: &mut Vec<Span>, column: &mut usize, text: &str, color: Option<Color>) {
+    let mut expanded = String::new();
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - *column % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            *column += spaces;
+        } else if !c.is_control() {
+            expanded.push(c);
+            *column += c.width().unwrap_or(0);
+        }
+    }
+    if expanded.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some((last, last_color)) if *last_color == color => last.push_str(&expanded),
+        _ => spans.push((expanded, color)),
+    }
+}
+
+fn plain(lines: Vec<String>) -> Vec<Vec<Span>> {
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut spans = vec![];
+            push_text(&mut spans, &mut 0, &line, None);
+            spans
+        })
+        .collect()
+}
+
+/// the entry's type, size, permissions, owner and age
+fn describe(path: &Path, metadata: &fs::Metadata) -> Vec<String> {
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        match fs::read_link(path) {
+            Ok(target) => format!("symlink to {}", target.display()),
+            Err(_) => "symlink".to_owned(),
+        }
+    } else if file_type.is_dir() {
+        "directory".to_owned()
+    } else if file_type.is_file() {
+        "file".to_owned()
+    } else if file_type.is_fifo() {
+        "fifo".to_owned()
+    } else if file_type.is_socket() {
+        "socket".to_owned()
+    } else if file_type.is_block_device() {
+        "block device".to_owned()
+    } else if file_type.is_char_device() {
+        "character device".to_owned()
+    } else {
+        "unknown".to_owned()
+    };
+    let mut lines = vec![
+        kind,
+        format!("size: {}", scan::format_size(metadata.len())),
+        format!("mode: {:03o}", metadata.permissions().mode() & 0o7777),
+        format!("owner: {}:{}", metadata.uid(), metadata.gid()),
+    ];
+    if let Ok(modified) = metadata.modified() {
+        if let Ok(age) = SystemTime::now().duration_since(modified) {
+            lines.push(format!("modified: {} ago", format_age(age.as_secs())));
+        }
+    }
+    lines
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
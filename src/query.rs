@@ -0,0 +1,738 @@
+use std::fs::{self, Metadata};
+use std::os::unix::fs::MetadataExt;
+use std::time::SystemTime;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::filter::FilterError;
+
+/// A filter expression on the entries' metadata, like `size>100M and mtime<7d`.
+///
+/// - predicates are `<field><op><value>`:
+///   - `size` (`=`, `!=`, `<`, `>`, `<=`, `>=`) in bytes, or with a unit: `B`, `K`, `M`, `G`, `T` (powers of 1024)
+///   - `mtime`, `atime`, `ctime` (same operators) how long ago, with a unit: `s`, `m`, `h`, `d`, `w`.
+///     `mtime<7d` means modified in the last 7 days, `mtime=2d` means modified 2 days ago (not 1, not 3).
+///   - `type` (`=`, `!=`) `file`, `dir` or `symlink`
+///   - `perm` (`=`, `!=`, `&`) an octal mode. `perm&002` means any of these bits are set.
+///   - `owner`, `group` (`=`, `!=`) a user or group name, or a numeric id
+///   - `ext` (`=`, `!=`) the file extension, case-insensitive. `ext=` matches files without one.
+/// - any other word is a (case-insensitive) regex which the name has to match.
+///   use quotes for regexes containing spaces or parentheses, like `"(a|b) c"`.
+/// - `and`, `or`, `not` and parentheses combine these. `and` binds stronger than `or`,
+///   and two expressions next to each other are combined with `and`.
+#[derive(Clone)]
+pub(crate) struct Query {
+    expr: Expr,
+}
+
+#[derive(Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Name(Regex),
+    /// `true` for `=`, `false` for `!=`
+    Ext(bool, String),
+    Metadata(Predicate),
+}
+/// the predicates which need the entry's metadata
+#[derive(Clone)]
+enum Predicate {
+    Size(Cmp, u64),
+    /// the value and the unit (in seconds)
+    Time(TimeField, Cmp, u64, u64),
+    Type(bool, Kind),
+    Perm(PermOp, u32),
+    Owner(bool, u32),
+    Group(bool, u32),
+}
+#[derive(Clone, Copy)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+#[derive(Clone, Copy)]
+enum TimeField {
+    Modified,
+    Accessed,
+    Changed,
+}
+#[derive(Clone, Copy)]
+enum Kind {
+    File,
+    Dir,
+    Symlink,
+}
+#[derive(Clone, Copy)]
+enum PermOp {
+    Eq,
+    Ne,
+    Any,
+}
+
+const FIELDS: [&str; 9] = [
+    "size", "mtime", "atime", "ctime", "type", "perm", "owner", "group", "ext",
+];
+/// longer operators first, so that `<=` isn't parsed as `<`
+const OPS: [&str; 7] = ["<=", ">=", "!=", "=", "<", ">", "&"];
+const SIZE_UNITS: [(char, u64); 5] = [
+    ('B', 1),
+    ('K', 1 << 10),
+    ('M', 1 << 20),
+    ('G', 1 << 30),
+    ('T', 1 << 40),
+];
+const TIME_UNITS: [(char, u64); 5] = [
+    ('s', 1),
+    ('m', 60),
+    ('h', 60 * 60),
+    ('d', 60 * 60 * 24),
+    ('w', 60 * 60 * 24 * 7),
+];
+
+impl Query {
+    pub fn new(text: &str) -> Result<Self, FilterError> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            index: 0,
+            end: text.chars().count(),
+        };
+        if parser.tokens.is_empty() {
+            return Err(parser.error(0, "empty query".to_owned()));
+        }
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.index) {
+            return Err(parser.error(token.pos, "unexpected ')'".to_owned()));
+        }
+        Ok(Self { expr })
+    }
+    /// entries whose metadata couldn't be read only match name regexes
+    pub fn matches(&self, name: &str, metadata: Option<&Metadata>) -> bool {
+        self.expr.matches(name, metadata, SystemTime::now())
+    }
+}
+
+impl Expr {
+    fn matches(&self, name: &str, metadata: Option<&Metadata>, now: SystemTime) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(name, metadata, now) && b.matches(name, metadata, now),
+            Self::Or(a, b) => a.matches(name, metadata, now) || b.matches(name, metadata, now),
+            Self::Not(a) => !a.matches(name, metadata, now),
+            Self::Name(regex) => regex.is_match(name.strip_suffix('/').unwrap_or(name)),
+            Self::Ext(eq, ext) => {
+                // when matching paths, only the last component has an extension
+                let name = name.strip_suffix('/').unwrap_or(name);
+                let name = name.rsplit('/').next().unwrap_or(name);
+                let actual = match name.rsplit_once('.') {
+                    // `.bashrc` is a hidden file, not a file with the extension `bashrc`
+                    Some((stem, ext)) if !stem.is_empty() => ext.to_lowercase(),
+                    _ => String::new(),
+                };
+                (actual == *ext) == *eq
+            }
+            Self::Metadata(predicate) => metadata.is_some_and(|m| predicate.matches(m, now)),
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, metadata: &Metadata, now: SystemTime) -> bool {
+        match self {
+            Self::Size(cmp, size) => cmp.apply(metadata.len(), *size),
+            Self::Time(field, cmp, value, unit) => {
+                let time = match field {
+                    TimeField::Modified => metadata.modified(),
+                    TimeField::Accessed => metadata.accessed(),
+                    TimeField::Changed => Ok(SystemTime::UNIX_EPOCH
+                        + std::time::Duration::from_secs(metadata.ctime().max(0) as u64)),
+                };
+                let age = match time {
+                    // times in the future count as 0 seconds ago
+                    Ok(time) => now.duration_since(time).map_or(0, |v| v.as_secs()),
+                    Err(_) => return false,
+                };
+                match cmp {
+                    // "2 days ago" is anything from 2 to 3 days ago
+                    Cmp::Eq | Cmp::Ne => cmp.apply(age / unit, *value),
+                    _ => cmp.apply(age, value.saturating_mul(*unit)),
+                }
+            }
+            Self::Type(eq, kind) => {
+                let file_type = metadata.file_type();
+                let is = match kind {
+                    Kind::File => file_type.is_file(),
+                    Kind::Dir => file_type.is_dir(),
+                    Kind::Symlink => file_type.is_symlink(),
+                };
+                is == *eq
+            }
+            Self::Perm(op, mode) => {
+                let actual = metadata.mode() & 0o7777;
+                match op {
+                    PermOp::Eq => actual == *mode,
+                    PermOp::Ne => actual != *mode,
+                    PermOp::Any => actual & mode != 0,
+                }
+            }
+            Self::Owner(eq, uid) => (metadata.uid() == *uid) == *eq,
+            Self::Group(eq, gid) => (metadata.gid() == *gid) == *eq,
+        }
+    }
+}
+
+impl Cmp {
+    fn apply(self, a: u64, b: u64) -> bool {
+        match self {
+            Self::Eq => a == b,
+            Self::Ne => a != b,
+            Self::Lt => a < b,
+            Self::Gt => a > b,
+            Self::Le => a <= b,
+            Self::Ge => a >= b,
+        }
+    }
+}
+
+struct Token {
+    /// index of the token's first char in the query
+    pos: usize,
+    kind: TokenKind,
+}
+enum TokenKind {
+    Open,
+    Close,
+    Word(String),
+    /// always a name regex, even if it looks like a predicate
+    Quoted(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, FilterError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().enumerate().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        let kind = match ch {
+            ch if ch.is_whitespace() => continue,
+            '(' => TokenKind::Open,
+            ')' => TokenKind::Close,
+            '"' | '\'' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, c)) if c == ch => break,
+                        Some((_, '\\')) if chars.peek().is_some_and(|(_, c)| *c == ch) => {
+                            word.push(ch);
+                            chars.next();
+                        }
+                        Some((_, c)) => word.push(c),
+                        None => {
+                            return Err(FilterError {
+                                position: Some(pos),
+                                message: "unclosed quote".to_owned(),
+                            })
+                        }
+                    }
+                }
+                TokenKind::Quoted(word)
+            }
+            ch => {
+                let mut word = String::from(ch);
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| !c.is_whitespace() && !matches!(c, '(' | ')'))
+                {
+                    word.push(c);
+                }
+                TokenKind::Word(word)
+            }
+        };
+        tokens.push(Token { pos, kind });
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    /// the length of the query, used as the position of errors at the end
+    end: usize,
+}
+impl Parser {
+    fn error(&self, pos: usize, message: String) -> FilterError {
+        FilterError {
+            position: Some(pos),
+            message,
+        }
+    }
+    fn word_at(&self, index: usize) -> Option<String> {
+        match self.tokens.get(index) {
+            Some(Token {
+                kind: TokenKind::Word(word),
+                ..
+            }) => Some(word.clone()),
+            _ => None,
+        }
+    }
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.index), Some(Token { kind: TokenKind::Word(w), .. }) if w == keyword)
+    }
+    fn parse_or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.index += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+    fn parse_and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.parse_not()?;
+        loop {
+            if self.peek_keyword("and") {
+                self.index += 1;
+            } else if self.peek_keyword("or")
+                || matches!(
+                    self.tokens.get(self.index),
+                    None | Some(Token {
+                        kind: TokenKind::Close,
+                        ..
+                    })
+                )
+            {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+    }
+    fn parse_not(&mut self) -> Result<Expr, FilterError> {
+        if self.peek_keyword("not") {
+            self.index += 1;
+            Ok(Expr::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+    fn parse_primary(&mut self) -> Result<Expr, FilterError> {
+        let token = match self.tokens.get(self.index) {
+            Some(v) => v,
+            None => return Err(self.error(self.end, "expected an expression".to_owned())),
+        };
+        let pos = token.pos;
+        self.index += 1;
+        match &token.kind {
+            TokenKind::Open => {
+                let expr = self.parse_or()?;
+                match self.tokens.get(self.index) {
+                    Some(Token {
+                        kind: TokenKind::Close,
+                        ..
+                    }) => {
+                        self.index += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(self.error(pos, "unclosed '('".to_owned())),
+                }
+            }
+            TokenKind::Close => {
+                Err(self.error(pos, "expected an expression, found ')'".to_owned()))
+            }
+            TokenKind::Quoted(regex) => name_regex(regex, pos + 1),
+            TokenKind::Word(word) => {
+                if matches!(word.as_str(), "and" | "or" | "not") {
+                    return Err(self.error(pos, format!("expected an expression, found '{word}'")));
+                }
+                let field_len = word.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+                let (field, rest) = word.split_at(field_len);
+                if rest.is_empty() && FIELDS.contains(&field) {
+                    // `size > 100M` or `size >100M`: join the following words
+                    let mut word = word.clone();
+                    // where each joined word starts, in `word` and in the query
+                    let mut starts = vec![(0, pos)];
+                    if let Some(next) = self
+                        .word_at(self.index)
+                        .filter(|next| OPS.iter().any(|op| next.starts_with(op)))
+                    {
+                        starts.push((field_len, self.tokens[self.index].pos));
+                        word.push_str(&next);
+                        self.index += 1;
+                        if OPS.contains(&&word[field_len..]) {
+                            if let Some(value) = self.word_at(self.index) {
+                                starts.push((word.chars().count(), self.tokens[self.index].pos));
+                                word.push_str(&value);
+                                self.index += 1;
+                            }
+                        }
+                    }
+                    // errors are at a position in `word`, which is shifted by the spaces which were left out
+                    return predicate(&word, 0).map_err(|mut e| {
+                        if let Some(offset) = e.position {
+                            let (start, pos) = starts
+                                .iter()
+                                .rev()
+                                .find(|(start, _)| *start <= offset)
+                                .unwrap();
+                            e.position = Some(pos + offset - start);
+                        }
+                        e
+                    });
+                }
+                if !field.is_empty() && OPS.iter().any(|op| rest.starts_with(op)) {
+                    predicate(word, pos)
+                } else {
+                    name_regex(word, pos)
+                }
+            }
+        }
+    }
+}
+
+fn name_regex(regex: &str, pos: usize) -> Result<Expr, FilterError> {
+    match RegexBuilder::new(regex).case_insensitive(true).build() {
+        Ok(regex) => Ok(Expr::Name(regex)),
+        Err(e) => {
+            let mut e = FilterError::from(e);
+            e.position = Some(pos + e.position.unwrap_or(0));
+            Err(e)
+        }
+    }
+}
+
+/// parses `<field><op><value>`. `pos` is the position of the first char.
+fn predicate(word: &str, pos: usize) -> Result<Expr, FilterError> {
+    let error = |offset: usize, message: String| FilterError {
+        position: Some(pos + offset),
+        message,
+    };
+    let field_len = word.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+    let (field, rest) = word.split_at(field_len);
+    if !FIELDS.contains(&field) {
+        return Err(error(
+            0,
+            format!(
+                "unknown field '{field}' (expected one of {})",
+                FIELDS.join(", ")
+            ),
+        ));
+    }
+    let op = match OPS.iter().find(|op| rest.starts_with(**op)) {
+        Some(v) => *v,
+        None => {
+            return Err(error(
+                field_len,
+                format!("expected an operator after '{field}', like {field}=..."),
+            ))
+        }
+    };
+    let value = &rest[op.len()..];
+    // the value's position
+    let value_pos = field_len + op.len();
+    if value.is_empty() && field != "ext" {
+        return Err(error(
+            value_pos,
+            format!("expected a value after '{field}{op}'"),
+        ));
+    }
+    let cmp = match op {
+        "=" => Cmp::Eq,
+        "!=" => Cmp::Ne,
+        "<" => Cmp::Lt,
+        ">" => Cmp::Gt,
+        "<=" => Cmp::Le,
+        ">=" => Cmp::Ge,
+        _ => Cmp::Eq,
+    };
+    let allowed: &[&str] = match field {
+        "size" | "mtime" | "atime" | "ctime" => &["=", "!=", "<", ">", "<=", ">="],
+        "perm" => &["=", "!=", "&"],
+        _ => &["=", "!="],
+    };
+    if !allowed.contains(&op) {
+        return Err(error(
+            field_len,
+            format!(
+                "'{op}' can't be used with '{field}' (expected one of: {})",
+                allowed.join(" ")
+            ),
+        ));
+    }
+    let eq = op == "=";
+    let predicate = match field {
+        "size" => match parse_size(value) {
+            Ok(v) => Predicate::Size(cmp, v),
+            Err((offset, message)) => return Err(error(value_pos + offset, message)),
+        },
+        "mtime" | "atime" | "ctime" => {
+            let (number, unit) = split_unit(value);
+            let unit = match unit.and_then(|u| TIME_UNITS.iter().find(|(c, _)| *c == u)) {
+                Some((_, v)) => *v,
+                None => {
+                    return Err(error(
+                        value_pos + number.len(),
+                        "expected a time unit: s, m, h, d or w".to_owned(),
+                    ))
+                }
+            };
+            let field = match field {
+                "mtime" => TimeField::Modified,
+                "atime" => TimeField::Accessed,
+                _ => TimeField::Changed,
+            };
+            match number.parse::<u64>() {
+                Ok(v) => Predicate::Time(field, cmp, v, unit),
+                Err(_) => return Err(error(value_pos, format!("invalid time '{value}'"))),
+            }
+        }
+        "type" => Predicate::Type(
+            eq,
+            match value {
+                "file" | "f" => Kind::File,
+                "dir" | "d" => Kind::Dir,
+                "symlink" | "link" | "l" => Kind::Symlink,
+                _ => {
+                    return Err(error(
+                        value_pos,
+                        format!("unknown type '{value}' (expected file, dir or symlink)"),
+                    ))
+                }
+            },
+        ),
+        "perm" => {
+            let digits = value.strip_prefix("0o").unwrap_or(value);
+            let mode = match u32::from_str_radix(digits, 8) {
+                Ok(v) if v <= 0o7777 => v,
+                _ => {
+                    return Err(error(
+                        value_pos,
+                        format!("invalid mode '{value}' (expected an octal number like 644)"),
+                    ))
+                }
+            };
+            let op = match op {
+                "&" => PermOp::Any,
+                "=" => PermOp::Eq,
+                _ => PermOp::Ne,
+            };
+            Predicate::Perm(op, mode)
+        }
+        "owner" | "group" => {
+            let file = if field == "owner" {
+                "/etc/passwd"
+            } else {
+                "/etc/group"
+            };
+            let id = match value.parse() {
+                Ok(v) => v,
+                Err(_) => match lookup_id(file, value) {
+                    Some(v) => v,
+                    None => {
+                        return Err(error(
+                            value_pos,
+                            format!(
+                                "unknown {} '{value}'",
+                                if field == "owner" { "user" } else { "group" }
+                            ),
+                        ))
+                    }
+                },
+            };
+            if field == "owner" {
+                Predicate::Owner(eq, id)
+            } else {
+                Predicate::Group(eq, id)
+            }
+        }
+        _ => {
+            return Ok(Expr::Ext(
+                eq,
+                value.strip_prefix('.').unwrap_or(value).to_lowercase(),
+            ))
+        }
+    };
+    Ok(Expr::Metadata(predicate))
+}
+
+/// parses a size like `100M`. on error, returns the position of the error in `value` and a message.
+pub(crate) fn parse_size(value: &str) -> Result<u64, (usize, String)> {
+    let (number, unit) = split_unit(value);
+    let unit = match unit {
+        None => 1,
+        Some(u) => match SIZE_UNITS.iter().find(|(c, _)| c.eq_ignore_ascii_case(&u)) {
+            Some((_, v)) => *v,
+            None => {
+                return Err((
+                    number.len(),
+                    format!("unknown size unit '{u}' (expected B, K, M, G or T)"),
+                ))
+            }
+        },
+    };
+    match number.parse::<u64>() {
+        Ok(v) => Ok(v.saturating_mul(unit)),
+        Err(_) => Err((0, format!("invalid size '{value}'"))),
+    }
+}
+
+/// splits `100M` into `100` and `M`
+fn split_unit(value: &str) -> (&str, Option<char>) {
+    match value.chars().last() {
+        Some(c) if c.is_ascii_alphabetic() => (&value[..value.len() - 1], Some(c)),
+        _ => (value, None),
+    }
+}
+
+/// finds the id of a user or group in `/etc/passwd` or `/etc/group`
+fn lookup_id(file: &str, name: &str) -> Option<u32> {
+    fs::read_to_string(file).ok()?.lines().find_map(|line| {
+        let mut parts = line.split(':');
+        if parts.next()? == name {
+            parts.nth(1)?.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::{parse_size, Query};
+
+    fn matches(query: &str, name: &str) -> bool {
+        Query::new(query).unwrap().matches(name, None)
+    }
+    /// the position and message of the error in `query`
+    fn error(query: &str) -> (Option<usize>, String) {
+        match Query::new(query) {
+            Ok(_) => panic!("'{query}' should be invalid"),
+            Err(e) => (e.position, e.message),
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert!(matches("foo", "a_FOO.txt"));
+        assert!(!matches("foo", "bar"));
+        assert!(matches("^a.*z$", "abcz"));
+        // directories end with `/`, which isn't part of the name
+        assert!(matches("dir$", "dir/"));
+        // quotes keep spaces and parentheses in the regex
+        assert!(matches("\"a b\"", "xa by"));
+        assert!(matches("\"(a|b)c\"", "bc"));
+        assert!(matches("'it\\'s'", "it's"));
+    }
+
+    #[test]
+    fn operators() {
+        assert!(matches("a and b", "ab"));
+        assert!(!matches("a and b", "a"));
+        // next to each other means `and`
+        assert!(matches("a b", "ba"));
+        assert!(!matches("a b", "b"));
+        assert!(matches("a or b", "b"));
+        assert!(!matches("a or b", "c"));
+        assert!(matches("not a", "b"));
+        assert!(!matches("not not a", "b"));
+        // `and` binds stronger than `or`
+        assert!(matches("a or b and c", "a"));
+        assert!(!matches("a or b and c", "b"));
+        assert!(!matches("(a or b) and c", "a"));
+        assert!(matches("(a or b) and c", "bc"));
+    }
+
+    #[test]
+    fn extensions() {
+        assert!(matches("ext=rs", "main.RS"));
+        assert!(matches("ext=.rs", "main.rs"));
+        assert!(!matches("ext=rs", "main.rs.bak"));
+        assert!(matches("ext!=rs", "main.c"));
+        // hidden files and files without a dot have no extension
+        assert!(matches("ext=", "Makefile"));
+        assert!(matches("ext=", ".bashrc"));
+        // only the last component of a path has an extension
+        assert!(matches("ext=", "lib.d/readme"));
+        assert!(matches("ext=md", "lib.d/readme.md"));
+    }
+
+    #[test]
+    fn without_metadata() {
+        // entries whose metadata couldn't be read only match name regexes
+        assert!(!matches("size>0", "a"));
+        assert!(matches("not size>0", "a"));
+        assert!(matches("size>0 or a", "a"));
+    }
+
+    #[test]
+    fn metadata() {
+        let path = std::env::temp_dir().join(format!("tuifile-query-{}", std::process::id()));
+        fs::write(&path, [0u8; 2048]).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        let metadata = fs::symlink_metadata(&path).unwrap();
+        let matches = |query: &str| Query::new(query).unwrap().matches("f", Some(&metadata));
+        assert!(matches("size=2K"));
+        assert!(matches("size=2048"));
+        assert!(matches("size>1k and size<=2048"));
+        assert!(!matches("size>2K"));
+        assert!(matches("size != 1K"));
+        assert!(matches("size >2047"));
+        assert!(matches("type=file"));
+        assert!(matches("type!=dir"));
+        assert!(!matches("type=symlink"));
+        assert!(matches("mtime<1h"));
+        assert!(matches("mtime=0d"));
+        assert!(!matches("mtime>1m"));
+        assert!(matches("perm=640"));
+        assert!(matches("perm=0o640"));
+        assert!(matches("perm&040"));
+        assert!(!matches("perm&002"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(""), (Some(0), "empty query".to_owned()));
+        assert_eq!(
+            error("size>"),
+            (Some(5), "expected a value after 'size>'".to_owned())
+        );
+        assert_eq!(error("size>10X").0, Some(7));
+        assert_eq!(error("size>lots").0, Some(8));
+        assert_eq!(error("a size>x10").0, Some(7));
+        assert_eq!(error("mtime<7x").0, Some(7));
+        assert_eq!(error("mtime<7").0, Some(7));
+        assert_eq!(error("type=sock").0, Some(5));
+        assert_eq!(error("perm=999").0, Some(5));
+        assert_eq!(error("perm=10000").0, Some(5));
+        assert_eq!(error("perm<644").0, Some(4));
+        assert_eq!(error("type>file").0, Some(4));
+        assert!(error("foo=1").1.starts_with("unknown field 'foo'"));
+        assert_eq!(error("a (b").0, Some(2));
+        assert_eq!(error("a )"), (Some(2), "unexpected ')'".to_owned()));
+        assert_eq!(error("a and").0, Some(5));
+        assert_eq!(error("a or not").0, Some(8));
+        assert_eq!(error("and a").0, Some(0));
+        assert_eq!(error("a ()").0, Some(3));
+        assert_eq!(error("x \"ab"), (Some(2), "unclosed quote".to_owned()));
+        // positions in joined words are in the query, not in the joined word
+        assert_eq!(error("size > 10X").0, Some(9));
+        assert_eq!(error("a  size  >  10X").0, Some(14));
+        assert_eq!(error("type = sock").0, Some(7));
+        assert_eq!(error("size >").0, Some(6));
+        assert_eq!(error("size >=x").0, Some(7));
+        // the position of a regex error is inside the quotes
+        assert_eq!(error("x \"a[\"").0, Some(4));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("12"), Ok(12));
+        assert_eq!(parse_size("12B"), Ok(12));
+        assert_eq!(parse_size("3k"), Ok(3 << 10));
+        assert_eq!(parse_size("100M"), Ok(100 << 20));
+        assert_eq!(parse_size("1T"), Ok(1 << 40));
+        assert_eq!(parse_size("5Q").map_err(|e| e.0), Err(1));
+        assert_eq!(parse_size("1.5M").map_err(|e| e.0), Err(0));
+    }
+}
@@ -1,14 +1,29 @@
-use crossterm::event::{poll, read, Event, KeyCode, KeyModifiers};
-use crossterm::style::{Attribute, Stylize};
+use crossterm::event::{
+    poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::style::{Attribute, StyledContent, Stylize};
 use crossterm::{cursor, queue, style, terminal, ExecutableCommand};
-use regex::RegexBuilder;
 
+use crate::clipboard::{Clipboard, ClipboardEntry};
+use crate::filter::{FilterError, FilterMatch, SearchFilter};
+use crate::grep::ContentSearch;
+use crate::history::HistorySearch;
+use crate::keymap::{self, Action, Context, HelpView};
+use crate::messages;
+use crate::path_bar;
+use crate::preview::Preview;
+use crate::saved::{SavedFilter, SavedPicker};
+use crate::scan::ScanOptions;
+use crate::tabs::{self, Tab};
 use crate::updates::Updates;
+use crate::width;
 use crate::{
-    tasks, AppCmd, BackgroundTask, DirContent, DirContentType, Focus, ScanFilesMode, Share,
+    scan, tasks, AppCmd, BackgroundTask, DirContent, DirContentType, Focus, Layout, ScanFilesMode,
+    Share,
 };
+use std::collections::HashSet;
 use std::io::Write;
-use std::os::unix::prelude::PermissionsExt;
 use std::path::PathBuf;
 use std::process::Command;
 use std::sync::{Arc, Mutex};
@@ -17,904 +32,2552 @@ use std::{fs, io};
 
 use crate::TuiFile;
 
-const BYTE_UNITS: [&'static str; 6] = ["", "K", "M", "G", "T", "P"];
+/// two clicks on the same entry within this time are a double click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
 
 impl TuiFile {
+    fn scan_options(&self, share: &Share) -> ScanOptions {
+        ScanOptions {
+            max_depth: self.scan_files_max_depth,
+            expanded: self.tree_mode.then(|| self.expanded.clone()),
+            show_hidden: self.show_hidden,
+            exclude: share.exclude.clone(),
+            one_file_system: share.one_file_system,
+            entry_limit: share.entry_limit,
+            cache: Arc::clone(&share.scan_cache),
+        }
+    }
+    /// tree mode: lists the contents of the directory at `i` below it.
+    fn tree_expand(&mut self, i: usize, share: &mut Share) {
+        let entry = &self.dir_content[i];
+        let path = entry.path.clone();
+        let depth = entry.rel_depth + 1;
+        self.expanded.insert(path.clone());
+        match scan::get_files_in(
+            &self.current_dir,
+            path.clone(),
+            depth,
+            0,
+            &self.scan_options(share),
+        ) {
+            Ok(v) => {
+                self.dir_content.splice(i + 1..i + 1, v);
+                self.updates.request_filter_files();
+            }
+            Err(err) => {
+                self.expanded.remove(&path);
+                share.messages.error(err);
+            }
+        }
+    }
+    /// tree mode: removes the contents of the directory at `i` from the list.
+    fn tree_collapse(&mut self, i: usize) {
+        let depth = self.dir_content[i].rel_depth;
+        let end = self.dir_content[i + 1..]
+            .iter()
+            .position(|e| e.rel_depth <= depth)
+            .map_or(self.dir_content.len(), |len| i + 1 + len);
+        self.dir_content.drain(i + 1..end);
+        self.expanded.remove(&self.dir_content[i].path);
+        self.selection_changed();
+        self.updates.request_filter_files();
+    }
+    /// the selected entries, relative to the current directory, see `tasks::selected_entries`.
+    /// in tree mode, only the expanded directories are listed.
+    pub fn selected_entries(&self) -> Vec<(PathBuf, bool)> {
+        tasks::selected_entries(&self.dir_content, &self.current_dir, |e| {
+            if self.tree_mode {
+                self.expanded.contains(&e.path)
+            } else {
+                e.rel_depth < self.scan_files_max_depth
+            }
+        })
+    }
+    /// compiles `search_text` after it was changed.
+    /// if it is invalid, the last valid filter stays in `search_filter` and the error is shown instead.
+    fn update_search_filter(&mut self) {
+        if self.search_text.is_empty() {
+            self.search_filter = None;
+            self.set_search_error(None);
+        } else {
+            match SearchFilter::new(self.filter_syntax, &self.search_text) {
+                Ok(filter) => {
+                    self.search_filter = Some(filter);
+                    self.set_search_error(None);
+                }
+                Err(e) => self.set_search_error(Some(e)),
+            }
+        }
+    }
+    /// the error is shown below the search bar
+    fn set_search_error(&mut self, error: Option<FilterError>) {
+        let had_error = self.search_error.is_some();
+        self.search_error = error;
+        if had_error != self.search_error.is_some() {
+            // the error line moves the search bar and takes space from the file list
+            self.updates.request_clear();
+            self.updates.request_redraw();
+        } else {
+            self.updates.request_redraw_searchbar();
+        }
+    }
+    /// the filter used by + and -
+    fn select_filter(&self) -> Result<SearchFilter, FilterError> {
+        let filter = SearchFilter::new(self.filter_syntax, &self.select_text)?;
+        if filter.content_regex().is_some() {
+            return Err("content searches can't be used to select entries"
+                .to_owned()
+                .into());
+        }
+        Ok(filter)
+    }
+    /// selects (or deselects) all listed entries matching `select_text`, even those which don't pass the filter
+    fn select_matching(&mut self, deselect: bool, share: &Share) -> Result<(), FilterError> {
+        let filter = self.select_filter()?;
+        for entry in &mut self.dir_content {
+            if !entry.is_entry() {
+                continue;
+            }
+            if filter.needs_metadata() {
+                entry.load(&share.info_what, &mut self.info_widths);
+            }
+            let matched = entry.filter_text(&self.current_dir, self.filter_paths);
+            if filter.find(&matched, entry.metadata()).is_some() {
+                entry.selected = !deselect;
+            }
+        }
+        self.selection_changed();
+        Ok(())
+    }
+    /// forgets the cached `selection_size`, after entries were (de)selected or removed from the list
+    fn selection_changed(&mut self) {
+        self.selection_size = None;
+        self.updates.request_redraw_filebar();
+    }
+    /// the number of selected entries, and the total size of the selected files
+    fn selection_size(&mut self, share: &Share) -> (usize, u64) {
+        if let Some(v) = self.selection_size {
+            return v;
+        }
+        let mut count = 0;
+        let mut size = 0;
+        for entry in &mut self.dir_content {
+            if entry.selected {
+                entry.load(&share.info_what, &mut self.info_widths);
+                count += 1;
+                if let DirContentType::File { metadata } = &entry.more {
+                    size += metadata.len();
+                }
+            }
+        }
+        self.selection_size = Some((count, size));
+        (count, size)
+    }
+    /// the text shown in the search bar, which is the history entry that was found while searching the history
+    fn search_bar_text(&self, share: &Share) -> String {
+        if let (Focus::SelectBar { deselect }, None) = (&self.focus, &self.history_search) {
+            return format!(
+                "{}select: {}",
+                if *deselect { "de" } else { "" },
+                self.select_text
+            );
+        }
+        match &self.history_search {
+            Some(search) => format!(
+                "(history) {}: {}",
+                search.query,
+                search
+                    .found
+                    .and_then(|i| share.history.get(i))
+                    .unwrap_or("")
+            ),
+            None => self.search_text.clone(),
+        }
+    }
+    /// replaces the search text, for example with an entry from the history
+    fn set_search_text(&mut self, text: String, share: &Share) {
+        self.search_text = text;
+        self.update_search_filter();
+        if share.live_search {
+            self.updates.request_filter_files();
+        }
+    }
+    /// the text typed into the select bar while it is shown, otherwise the search text
+    fn prompt_text(&self) -> &str {
+        match self.focus {
+            Focus::SelectBar { .. } => &self.select_text,
+            _ => &self.search_text,
+        }
+    }
+    /// replaces `prompt_text`, for example with an entry from the history
+    fn set_prompt_text(&mut self, text: String, share: &Share) {
+        match self.focus {
+            Focus::SelectBar { .. } => {
+                self.select_text = text;
+                self.set_search_error(self.select_filter().err());
+            }
+            _ => self.set_search_text(text, share),
+        }
+    }
+    /// replaces the filter stack and search text with the saved ones, and selects the saved files (if there are any)
+    fn recall_saved_filter(&mut self, saved: SavedFilter, share: &mut Share) {
+        let mut filter_stack = vec![];
+        for text in &saved.filters {
+            // the same filters as Ctrl+A can push
+            let error = match SearchFilter::new(self.filter_syntax, text) {
+                Ok(filter) if filter.content_regex().is_some() => {
+                    "content searches can't be stacked".to_owned()
+                }
+                Ok(filter) => {
+                    filter_stack.push(filter);
+                    continue;
+                }
+                Err(e) => e.to_string(),
+            };
+            // without a position, it would be one in the saved text, not in the search bar
+            let message = format!("saved filter '{}': {text}: {error}", saved.name);
+            self.set_search_error(Some(message.into()));
+            return;
+        }
+        self.filter_stack = filter_stack;
+        self.selected_filter = None;
+        self.search_text = saved.search;
+        self.update_search_filter();
+        if !saved.selection.is_empty() {
+            let selection: HashSet<PathBuf> = saved.selection.into_iter().collect();
+            for entry in &mut self.dir_content {
+                if entry.is_entry() {
+                    entry.selected = entry
+                        .path
+                        .strip_prefix(&self.current_dir)
+                        .is_ok_and(|path| selection.contains(path));
+                }
+            }
+            self.selection_changed();
+        }
+        share
+            .messages
+            .info(format!("recalled saved filter '{}'", saved.name));
+        self.updates.request_filter_files();
+    }
+    /// handles a key press while the clipboard is shown
+    fn clipboard_view_key(&mut self, e: KeyEvent, share: &mut Share) {
+        let (index, clipboard) = match (self.clipboard_view, &mut share.clipboard) {
+            (Some(index), Some(clipboard)) => (index, clipboard),
+            _ => {
+                self.clipboard_view = None;
+                return;
+            }
+        };
+        self.updates.request_redraw_filelist();
+        match e.code {
+            KeyCode::Up => self.clipboard_view = Some(index.saturating_sub(1)),
+            KeyCode::Down => {
+                if index + 1 < clipboard.entries.len() {
+                    self.clipboard_view = Some(index + 1);
+                }
+            }
+            KeyCode::Tab => {
+                clipboard.cut = !clipboard.cut;
+                self.updates.request_redraw_infobar();
+            }
+            KeyCode::Delete | KeyCode::Backspace => {
+                if index < clipboard.entries.len() {
+                    clipboard.entries.remove(index);
+                }
+                if clipboard.entries.is_empty() {
+                    share.clipboard = None;
+                    self.clipboard_view = None;
+                } else {
+                    self.clipboard_view = Some(index.min(clipboard.entries.len() - 1));
+                }
+                self.updates.request_redraw_infobar();
+            }
+            _ => self.clipboard_view = None,
+        }
+    }
+    /// handles a key press while the message log is shown
+    fn message_log_key(&mut self, e: KeyEvent) {
+        let scroll = match self.message_log {
+            Some(v) => v,
+            None => return,
+        };
+        self.updates.request_redraw_filelist();
+        self.message_log = match e.code {
+            KeyCode::Up => Some(scroll.saturating_sub(1)),
+            // limited to the number of messages when drawing
+            KeyCode::Down => Some(scroll + 1),
+            _ => None,
+        };
+    }
+    /// handles a key press while the keys are shown
+    fn help_key(&mut self, e: KeyEvent) {
+        let help = match &mut self.help {
+            Some(v) => v,
+            None => return,
+        };
+        self.updates.request_redraw_filelist();
+        match e.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::F(1) => self.help = None,
+            KeyCode::Up => help.scroll = help.scroll.saturating_sub(1),
+            // limited to the number of lines when drawing
+            KeyCode::Down => help.scroll += 1,
+            KeyCode::Char(ch) => {
+                help.filter.push(ch);
+                help.scroll = 0;
+            }
+            KeyCode::Backspace => {
+                help.filter.pop();
+                help.scroll = 0;
+            }
+            _ => {}
+        }
+    }
+    /// handles a key press while the saved filters are shown
+    fn saved_picker_key(&mut self, e: KeyEvent, share: &mut Share) {
+        let mut picker = match self.saved_picker.take() {
+            Some(v) => v,
+            None => return,
+        };
+        self.updates.request_redraw_filelist();
+        let result = match e.code {
+            KeyCode::Esc => return,
+            KeyCode::Up => {
+                picker.index = picker.index.saturating_sub(1);
+                Ok(())
+            }
+            KeyCode::Down => {
+                if picker.index + 1 < share.saved_filters.list.len() {
+                    picker.index += 1;
+                }
+                Ok(())
+            }
+            KeyCode::Tab => {
+                picker.with_selection = !picker.with_selection;
+                Ok(())
+            }
+            KeyCode::Char(ch) => {
+                picker.name.push(ch);
+                Ok(())
+            }
+            KeyCode::Backspace => {
+                picker.name.pop();
+                Ok(())
+            }
+            KeyCode::Delete => {
+                let result = share.saved_filters.remove(picker.index);
+                picker.index = picker
+                    .index
+                    .min(share.saved_filters.list.len().saturating_sub(1));
+                result
+            }
+            // Enter without a name -> recall
+            KeyCode::Enter if picker.name.is_empty() => {
+                if let Some(saved) = share.saved_filters.list.get(picker.index).cloned() {
+                    self.recall_saved_filter(saved, share);
+                }
+                return;
+            }
+            // Enter -> save
+            KeyCode::Enter => {
+                let selection = if picker.with_selection {
+                    self.dir_content
+                        .iter()
+                        .filter(|e| e.selected && e.is_entry())
+                        .filter_map(|e| {
+                            Some(e.path.strip_prefix(&self.current_dir).ok()?.to_owned())
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
+                let name = std::mem::take(&mut picker.name);
+                let result = share.saved_filters.set(SavedFilter {
+                    name: name.clone(),
+                    filters: self.filter_stack.iter().map(|f| f.label()).collect(),
+                    search: self
+                        .search_filter
+                        .as_ref()
+                        .map(|f| f.label())
+                        .unwrap_or_default(),
+                    selection,
+                });
+                if result.is_ok() {
+                    share.messages.info(format!("saved filter '{name}'"));
+                    return;
+                }
+                result
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            share.messages.error(e);
+        }
+        self.saved_picker = Some(picker);
+    }
+    /// enters the directory under the cursor (expands it in tree mode), or loads more entries
+    fn enter_current(&mut self, share: &mut Share) {
+        // expanding and loading more insert entries at their position in the tree
+        self.restore_scan_order();
+        if let Some(entry) = self.dir_content.get(self.current_index) {
+            if entry.is_entry() && self.tree_mode {
+                // Right/L in tree mode -> Expand directory or go to first entry
+                if self.expanded.contains(&entry.path) {
+                    self.set_current_index_to_visible(self.current_index + 1, true);
+                } else if entry.path.is_dir() {
+                    self.tree_expand(self.current_index, share);
+                }
+            } else if entry.is_entry() {
+                self.current_dir = entry.path.clone();
+                self.updates = u32::MAX;
+            } else {
+                // load more entries
+                match scan::get_more_files(&self.current_dir, entry, &self.scan_options(share)) {
+                    Ok(more) => {
+                        self.dir_content
+                            .splice(self.current_index..=self.current_index, more);
+                        self.updates.request_filter_files();
+                    }
+                    Err(err) => share.messages.error(err),
+                }
+            }
+        }
+    }
+    /// goes to `dir`, one of the directories the current one is in, and selects the directory it came from
+    fn go_to_ancestor(&mut self, dir: PathBuf) {
+        let child = match self.current_dir.strip_prefix(&dir) {
+            Ok(rest) => rest.components().next(),
+            Err(_) => None,
+        };
+        if let Some(child) = child {
+            let child = child.as_os_str().to_string_lossy().into_owned();
+            self.path_jump = false;
+            self.current_dir = dir;
+            self.updates.request_redraw_infobar();
+            self.request_rescan_files_then_select_by_name(child);
+        }
+    }
+    fn mouse_event(&mut self, e: MouseEvent, share: &mut Share) -> Option<AppCmd> {
+        let inside = (share.origin.0..share.origin.0 + share.size.0).contains(&e.column)
+            && (share.origin.1..share.origin.1 + share.size.1).contains(&e.row);
+        if !inside && matches!(e.kind, MouseEventKind::Down(_)) {
+            // clicking a tab or another pane makes its instance the active one
+            let tab = match &share.tab_bar {
+                Some(tab_bar) if e.row == 0 => tab_bar.tab_at(e.column),
+                _ => None,
+            };
+            return tab
+                .or_else(|| share.pane_at(e.column, e.row))
+                .map(AppCmd::FocusInstance);
+        }
+        let column = e.column.saturating_sub(share.origin.0);
+        let row = e.row.saturating_sub(share.origin.1);
+        // the file list starts below the info bar and the file bar, the scrollbar is in its last column
+        let list_width = share.size.0 - self.preview_width(share);
+        let list_row = (row as usize)
+            .checked_sub(2)
+            .filter(|row| *row < self.last_drawn_files_height);
+        // indices of the visible entries
+        let visible: Vec<usize> = self
+            .dir_content
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.passes_filter)
+            .map(|(i, _)| i)
+            .collect();
+        let scroll_pos = visible.partition_point(|i| *i < self.scroll);
+        let max_scroll_pos = visible.len().saturating_sub(self.last_drawn_files_height);
+        match e.kind {
+            MouseEventKind::ScrollDown => self.scroll_files(&visible, scroll_pos + 3),
+            MouseEventKind::ScrollUp => self.scroll_files(&visible, scroll_pos.saturating_sub(3)),
+            MouseEventKind::Down(MouseButton::Left)
+                if column + 1 == list_width && max_scroll_pos > 0 && list_row.is_some() =>
+            {
+                self.dragging_scrollbar = true;
+                self.drag_scrollbar(&visible, row);
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging_scrollbar => {
+                self.drag_scrollbar(&visible, row);
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging_scrollbar = false,
+            // clicking a directory in the path bar goes there
+            MouseEventKind::Down(MouseButton::Left) if row == 0 => {
+                if let Some(dir) = self.path_bar.path_at(e.column) {
+                    self.go_to_ancestor(dir.to_owned());
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) if column < list_width => {
+                let index = match list_row.and_then(|row| visible.get(scroll_pos + row)) {
+                    Some(i) => *i,
+                    None => return None,
+                };
+                if e.modifiers.contains(KeyModifiers::CONTROL) {
+                    // Ctrl+click -> toggle the selection of the clicked entry
+                    let entry = &mut self.dir_content[index];
+                    if entry.is_entry() {
+                        entry.selected = !entry.selected;
+                    }
+                    self.updates.request_redraw_filelist();
+                    self.selection_changed();
+                } else if e.modifiers.contains(KeyModifiers::SHIFT) {
+                    // Shift+click -> select the visible entries between the cursor and the clicked entry
+                    let (start, end) = if index < self.current_index {
+                        (index, self.current_index)
+                    } else {
+                        (self.current_index, index)
+                    };
+                    for entry in self.dir_content.iter_mut().take(end + 1).skip(start) {
+                        if entry.passes_filter && entry.is_entry() {
+                            entry.selected = true;
+                        }
+                    }
+                    self.updates.request_redraw_filelist();
+                    self.selection_changed();
+                } else if self
+                    .last_click
+                    .is_some_and(|(time, i)| i == index && time.elapsed() < DOUBLE_CLICK_TIME)
+                {
+                    // double click -> enter the directory, like Right/L
+                    self.last_click = None;
+                    self.current_index = index;
+                    self.enter_current(share);
+                    return None;
+                }
+                self.last_click = Some((Instant::now(), index));
+                self.current_index = index;
+                self.updates.request_move_cursor();
+            }
+            _ => {}
+        }
+        None
+    }
+    /// scrolls the file list so the visible entry at `pos` is at the top, keeping the cursor on screen
+    fn scroll_files(&mut self, visible: &[usize], pos: usize) {
+        let pos = pos.min(visible.len().saturating_sub(self.last_drawn_files_height));
+        let first = match visible.get(pos) {
+            Some(i) => *i,
+            None => return,
+        };
+        let last = visible[(pos + self.last_drawn_files_height)
+            .saturating_sub(1)
+            .min(visible.len() - 1)];
+        self.scroll = first;
+        self.current_index = self.current_index.clamp(first, last);
+        if !self.dir_content[self.current_index].passes_filter {
+            // the cursor was on a hidden entry between two visible ones
+            self.current_index = visible[visible.partition_point(|i| *i < self.current_index)];
+        }
+        self.updates.request_redraw_filelist();
+        self.updates.request_move_cursor();
+    }
+    /// scrolls the file list so the scrollbar's thumb is on `row`
+    fn drag_scrollbar(&mut self, visible: &[usize], row: u16) {
+        let max_pos = visible.len().saturating_sub(self.last_drawn_files_height);
+        let row = (row as usize)
+            .saturating_sub(2)
+            .min(self.last_drawn_files_height.saturating_sub(1));
+        let pos = (max_pos * row)
+            .checked_div(self.last_drawn_files_height.saturating_sub(1))
+            .unwrap_or(0);
+        self.scroll_files(visible, pos);
+    }
+    /// the width of the preview pane, including the line separating it from the file list. 0 if it isn't shown.
+    fn preview_width(&self, share: &Share) -> u16 {
+        if self.show_preview && share.size.0 >= 20 {
+            (share.size.0 as u32 * share.preview_width as u32 / 100) as u16
+        } else {
+            0
+        }
+    }
+    /// the error line and the message line below the search bar, if there are any, are the last lines
+    fn search_bar_line(&self, share: &Share) -> u16 {
+        share
+            .size
+            .1
+            .saturating_sub(1 + self.search_error.is_some() as u16 + self.message_line as u16)
+    }
     pub fn term_setup(&mut self, share: &mut Share) -> io::Result<()> {
         self.updates.request_redraw();
         Self::term_setup_no_redraw(share)
     }
     pub fn term_setup_no_redraw(share: &mut Share) -> io::Result<()> {
         share.stdout.execute(terminal::EnterAlternateScreen)?;
+        share.stdout.execute(EnableMouseCapture)?;
         terminal::enable_raw_mode()?;
         Ok(())
     }
     pub fn term_reset(share: &mut Share) -> io::Result<()> {
         terminal::disable_raw_mode()?;
+        share.stdout.execute(DisableMouseCapture)?;
         share.stdout.execute(terminal::LeaveAlternateScreen)?;
         Ok(())
     }
-    pub fn run(&mut self, share: &mut Share) -> io::Result<AppCmd> {
-        loop {
-            if let Some(rescan) = share.check_bgtasks() {
-                if let Some(task) = &self.dir_content_builder_task {
-                    if let Some(v) = {
-                        let mut temp = task.lock().unwrap();
-                        temp.take()
-                    } {
-                        self.dir_content_builder_task = None;
-                        after_rescanning_files(self, v);
+    /// applies the changes requested in `updates`, like rescanning or filtering the files
+    fn update(&mut self, share: &mut Share) {
+        // the listing finished in the background
+        if let Some(task) = &self.dir_content_builder_task {
+            if let Some(v) = {
+                let mut temp = task.lock().unwrap();
+                temp.take()
+            } {
+                self.dir_content_builder_task = None;
+                after_rescanning_files(self, v);
+            }
+        }
+        // the content search found more files
+        if let Some(search) = &self.content_search {
+            let mut results = search.results.lock().unwrap();
+            if results.changed {
+                results.changed = false;
+                self.updates.request_filter_files();
+            }
+        }
+        // rescan files if necessary
+        fn after_rescanning_files(s: &mut TuiFile, v: Result<Vec<DirContent>, String>) {
+            s.updates.request_rescanning_files_complete();
+            s.updates.request_filter_files();
+            match v {
+                Ok(v) => {
+                    s.dir_content = v;
+                    s.ranked = false;
+                    s.selection_changed();
+                }
+                Err(err) => {
+                    s.files_status_is_special = true;
+                    s.files_status = err;
+                }
+            }
+        }
+        if self.updates.rescan_files() {
+            self.updates.dont_rescan_files();
+            if self.dir_content_builder_task.is_none() {
+                self.dir_content.clear();
+                self.selection_changed();
+                self.info_widths.clear();
+                self.files_status_is_special = false;
+                let (scan_dir_blocking, mut scan_dir_threaded, timeout) = match self.scan_files_mode
+                {
+                    ScanFilesMode::Blocking => (true, false, None),
+                    ScanFilesMode::Threaded => (false, true, None),
+                    ScanFilesMode::Timeout(t) => (true, false, Some(t)),
+                    ScanFilesMode::TimeoutThenThreaded(t) => (true, true, Some(t)),
+                };
+                let options = self.scan_options(share);
+                if scan_dir_blocking {
+                    let v = scan::get_files(self.current_dir.clone(), &options, timeout);
+                    if v.as_ref().is_ok_and(|v| v.1) {
+                        // completed, no need for the threaded fallback.
+                        scan_dir_threaded = false;
+                    }
+                    if !scan_dir_threaded {
+                        after_rescanning_files(self, v.map(|v| v.0));
                     }
                 }
-                if rescan {
-                    return Ok(AppCmd::RescanFiles);
+                if scan_dir_threaded {
+                    let dir = self.current_dir.clone();
+                    let arc = Arc::new(Mutex::new(None));
+                    self.dir_content_builder_task = Some(Arc::clone(&arc));
+                    self.updates.request_redraw_filelist();
+                    self.updates.request_redraw_infobar();
+                    share.tasks.push(BackgroundTask::new(
+                        "listing files...".to_string(),
+                        move |_status| {
+                            let v = scan::get_files(dir, &options, None).map(|v| v.0);
+                            *arc.lock().unwrap() = Some(v);
+                            Ok(None)
+                        },
+                        false,
+                    ));
                 }
             }
-            // rescan files if necessary
-            fn after_rescanning_files(s: &mut TuiFile, v: Result<Vec<DirContent>, String>) {
-                s.updates.request_rescanning_files_complete();
-                s.updates.request_filter_files();
-                match v {
-                    Ok(v) => s.dir_content = v,
-                    Err(err) => {
-                        s.files_status_is_special = true;
-                        s.files_status = err;
-                    }
-                }
-            }
-            if self.updates.rescan_files() {
-                self.updates.dont_rescan_files();
-                if self.dir_content_builder_task.is_none() {
-                    self.dir_content.clear();
-                    self.files_status_is_special = false;
-                    let (scan_dir_blocking, mut scan_dir_threaded, timeout) =
-                        match self.scan_files_mode {
-                            ScanFilesMode::Blocking => (true, false, None),
-                            ScanFilesMode::Threaded => (false, true, None),
-                            ScanFilesMode::Timeout(t) => (true, false, Some(t)),
-                            ScanFilesMode::TimeoutThenThreaded(t) => (true, true, Some(t)),
-                        };
-                    if scan_dir_blocking {
-                        let v = get_files(
-                            self.current_dir.clone(),
-                            self.scan_files_max_depth,
-                            &share.info_what,
-                            timeout,
-                        );
-                        if v.as_ref().is_ok_and(|v| v.1) {
-                            // completed, no need for the threaded fallback.
-                            scan_dir_threaded = false;
-                        }
-                        if !scan_dir_threaded {
-                            after_rescanning_files(self, v.map(|v| v.0));
+        }
+        if self.updates.rescanning_files_complete() {
+            self.updates.dont_rescanning_files_complete();
+            if self.current_index >= self.dir_content.len()
+                || self.dir_content[self.current_index].passes_filter
+            {
+                self.set_current_index_to_visible(self.dir_content.len().saturating_sub(1), false);
+            }
+            if !self.after_rescanning_files.is_empty() {
+                for func in std::mem::take(&mut self.after_rescanning_files) {
+                    func(self);
+                }
+            }
+        }
+        if self.updates.reset_search() {
+            self.updates.dont_reset_search();
+            if !self.search_text.is_empty() {
+                self.search_text.clear();
+                self.update_search_filter();
+                self.updates.request_filter_files();
+            }
+        }
+        if self.updates.filter_files() {
+            self.updates.request_redraw_filelist();
+            let was_ranked = self.ranked;
+            self.restore_scan_order();
+            self.updates.dont_filter_files();
+            for (i, entry) in self.dir_content.iter_mut().enumerate() {
+                entry.order = i;
+            }
+            if self.search_filter.is_some() || !self.filter_stack.is_empty() {
+                let filter = self.search_filter.as_ref();
+                match filter.and_then(|f| f.content_regex()) {
+                    Some(regex) => {
+                        // searching all files again is only necessary if the listed files changed
+                        let paths = self
+                            .dir_content
+                            .iter()
+                            .filter(|e| e.is_entry() && !e.name.ends_with('/'))
+                            .map(|e| e.path.clone())
+                            .collect::<Vec<_>>();
+                        if !self
+                            .content_search
+                            .as_ref()
+                            .is_some_and(|s| s.is_for(regex, &paths))
+                        {
+                            self.content_search = Some(ContentSearch::start(
+                                regex.clone(),
+                                paths,
+                                share.content_search_max_size,
+                                share,
+                            ));
+                            self.updates.request_redraw_infobar();
                         }
                     }
-                    if scan_dir_threaded {
-                        let dir = self.current_dir.clone();
-                        let max_depth = self.scan_files_max_depth;
-                        let info_what = share.info_what.clone();
-                        let arc = Arc::new(Mutex::new(None));
-                        self.dir_content_builder_task = Some(Arc::clone(&arc));
-                        self.updates.request_redraw_filelist();
-                        self.updates.request_redraw_infobar();
-                        share.tasks.push(BackgroundTask::new(
-                            "listing files...".to_string(),
-                            move |_status| {
-                                let v = get_files(dir, max_depth, &info_what, None).map(|v| v.0);
-                                *arc.lock().unwrap() = Some(v);
-                                Ok(())
-                            },
-                            false,
-                        ));
+                    None => self.content_search = None,
+                }
+                let content_results = self
+                    .content_search
+                    .as_ref()
+                    .map(|s| s.results.lock().unwrap());
+                if filter.is_some_and(|f| f.needs_metadata())
+                    || self.filter_stack.iter().any(|f| f.needs_metadata())
+                {
+                    for entry in &mut self.dir_content {
+                        entry.load(&share.info_what, &mut self.info_widths);
                     }
-                    fn get_files(
-                        dir: PathBuf,
-                        max_depth: usize,
-                        info_what: &Vec<u32>,
-                        timeout: Option<f32>,
-                    ) -> Result<(Vec<DirContent>, bool), String> {
-                        let mut o = vec![];
-                        let completed = get_files(
-                            &mut o,
-                            dir,
-                            0,
-                            max_depth,
-                            info_what,
-                            timeout.map(|v| (Instant::now(), v)),
-                        )?;
-                        // table-style
-                        let mut lengths = vec![];
-                        for e in o.iter() {
-                            for (i, line) in e.info.lines().enumerate() {
-                                if i >= lengths.len() {
-                                    lengths.push(0);
-                                }
-                                if line.len() > lengths[i] {
-                                    lengths[i] = line.len();
-                                }
-                            }
-                        }
-                        for e in o.iter_mut() {
-                            let src = std::mem::replace(&mut e.info, String::new());
-                            for (i, line) in src.lines().enumerate() {
-                                let rem = lengths[i] - line.len();
-                                if line.starts_with('<') {
-                                    e.info.push_str(&line[1..]);
-                                    for _ in 0..rem {
-                                        e.info.push(' ');
-                                    }
-                                } else if line.starts_with('>') {
-                                    for _ in 0..rem {
-                                        e.info.push(' ');
-                                    }
-                                    e.info.push_str(&line[1..]);
-                                } else {
-                                    let r = rem / 2;
-                                    for _ in 0..r {
-                                        e.info.push(' ');
-                                    }
-                                    e.info.push_str(&line[1..]);
-                                    for _ in 0..(rem - r) {
-                                        e.info.push(' ');
-                                    }
-                                }
-                            }
+                }
+                self.dir_content_len = 0;
+                // only used by ranked filters, indexed by `order`
+                let mut scores = vec![];
+                for entry in &mut self.dir_content {
+                    entry.context = false;
+                    let matched = entry.filter_text(&self.current_dir, self.filter_paths);
+                    let matched = matched.as_ref();
+                    // only the name is drawn, which is at the end of the path.
+                    // the `... more not loaded` entries' names aren't, and they are never highlighted.
+                    let offset = matched
+                        .chars()
+                        .count()
+                        .saturating_sub(entry.name.chars().count());
+                    // the filters on the stack are applied first, then the one in the search bar
+                    let m = if !entry.is_entry()
+                        || !self
+                            .filter_stack
+                            .iter()
+                            .all(|f| f.find(matched, entry.metadata()).is_some())
+                    {
+                        None
+                    } else if let Some(results) = &content_results {
+                        results
+                            .files
+                            .contains_key(&entry.path)
+                            .then(|| FilterMatch {
+                                score: 0,
+                                highlight: vec![],
+                            })
+                    } else if let Some(filter) = filter {
+                        filter.find(matched, entry.metadata())
+                    } else {
+                        Some(FilterMatch {
+                            score: 0,
+                            highlight: vec![],
+                        })
+                    };
+                    entry.passes_filter = !entry.is_entry() || m.is_some();
+                    if entry.passes_filter {
+                        self.dir_content_len += 1;
+                    }
+                    let (score, highlight) =
+                        m.map_or((i64::MIN, vec![]), |m| (m.score, m.highlight));
+                    entry.highlight = highlight
+                        .into_iter()
+                        .filter_map(|i| i.checked_sub(offset))
+                        .collect();
+                    scores.push(score);
+                }
+                if self.filter_ancestors && !filter.is_some_and(|f| f.ranked()) {
+                    // the entries are listed depth-first, so going up from an entry,
+                    // the first entry with a lower depth is its parent.
+                    // going up, entries with a depth lower than `depth` are ancestors of a match.
+                    let mut depth = 0;
+                    for entry in self.dir_content.iter_mut().rev() {
+                        if !entry.is_entry() {
+                            continue;
                         }
-                        fn get_files(
-                            dir_content: &mut Vec<DirContent>,
-                            dir: PathBuf,
-                            depth: usize,
-                            max_depth: usize,
-                            info_what: &Vec<u32>,
-                            time_limit: Option<(Instant, f32)>,
-                        ) -> Result<bool, String> {
-                            match fs::read_dir(&dir) {
-                                Err(e) => {
-                                    if depth == 0 {
-                                        return Err(format!("{e}"));
-                                    }
-                                }
-                                Ok(files) => {
-                                    for entry in files {
-                                        if let Ok(entry) = entry {
-                                            let mut name =
-                                                entry.file_name().to_string_lossy().into_owned();
-                                            let metadata = entry.metadata();
-                                            let p = entry.path();
-                                            let info = if let Ok(metadata) = &metadata {
-                                                // in each line:
-                                                // first char:
-                                                // < left-aligned
-                                                // > right-aligned
-                                                // anything else -> centered
-                                                // sep. line: "< | "
-                                                let mut info = String::new();
-                                                for info_what in info_what {
-                                                    match info_what {
-                                                        0 => {
-                                                            let mut bytes = metadata.len();
-                                                            let mut i = 0;
-                                                            loop {
-                                                                if bytes < 1024
-                                                                    || i + 1 >= BYTE_UNITS.len()
-                                                                {
-                                                                    info.push_str(&format!(
-                                                                        "< | \n>{bytes}\n>{}\n",
-                                                                        BYTE_UNITS[i]
-                                                                    ));
-                                                                    break;
-                                                                } else {
-                                                                    i += 1;
-                                                                    // divide by 1024 but cooler
-                                                                    bytes >>= 10;
-                                                                }
-                                                            }
-                                                        }
-                                                        1 => {
-                                                            info.push_str(&format!(
-                                                                "< | \n>{:03o}\n",
-                                                                metadata.permissions().mode()
-                                                                    & 0o777,
-                                                            ));
-                                                        }
-                                                        _ => {}
-                                                    }
-                                                }
-                                                info
-                                            } else {
-                                                String::new()
-                                            };
-                                            let more = match metadata {
-                                                Err(e) => DirContentType::Err(e.to_string()),
-                                                Ok(metadata) => {
-                                                    if metadata.is_symlink() {
-                                                        DirContentType::Symlink { metadata }
-                                                    } else if metadata.is_file() {
-                                                        DirContentType::File { metadata }
-                                                    } else if metadata.is_dir() {
-                                                        DirContentType::Dir { metadata }
-                                                    } else {
-                                                        DirContentType::Err(format!(
-                                                            "not a file, dir or symlink"
-                                                        ))
-                                                    }
-                                                }
-                                            };
-                                            if let DirContentType::Dir { .. } = more {
-                                                name.push('/');
-                                            }
-                                            dir_content.push(DirContent {
-                                                path: entry.path(),
-                                                name_charlen: name.chars().count(),
-                                                name,
-                                                rel_depth: depth,
-                                                passes_filter: true,
-                                                selected: false,
-                                                info,
-                                                more,
-                                            });
-                                            if let Some((since, max)) = time_limit {
-                                                if since.elapsed().as_secs_f32() > max {
-                                                    return Ok(false);
-                                                }
-                                            }
-                                            if depth < max_depth {
-                                                // should (almost?) never return an error
-                                                get_files(
-                                                    dir_content,
-                                                    p,
-                                                    depth + 1,
-                                                    max_depth,
-                                                    info_what,
-                                                    time_limit,
-                                                )?;
-                                            }
-                                        }
-                                    }
-                                }
+                        if entry.rel_depth < depth {
+                            if !entry.passes_filter {
+                                entry.passes_filter = true;
+                                entry.context = true;
+                                self.dir_content_len += 1;
                             }
-                            Ok(true)
+                            depth = entry.rel_depth;
+                        } else if entry.passes_filter {
+                            depth = entry.rel_depth;
                         }
-                        Ok((o, completed))
                     }
                 }
-            }
-            if self.updates.rescanning_files_complete() {
-                self.updates.dont_rescanning_files_complete();
-                if self.current_index >= self.dir_content.len()
-                    || self.dir_content[self.current_index].passes_filter
+                if content_results.is_some()
+                    && !self
+                        .dir_content
+                        .get(self.current_index)
+                        .is_some_and(|e| e.passes_filter)
                 {
-                    self.set_current_index_to_visible(
-                        self.dir_content.len().saturating_sub(1),
-                        false,
-                    );
+                    // files are found while the search is running, select the first one
+                    self.updates.request_reset_current_index();
                 }
-                if !self.after_rescanning_files.is_empty() {
-                    for func in std::mem::replace(&mut self.after_rescanning_files, vec![]) {
-                        func(self);
-                    }
+                drop(content_results);
+                if filter.is_some_and(|f| f.ranked()) {
+                    self.dir_content
+                        .sort_by_key(|e| (std::cmp::Reverse(scores[e.order]), e.order));
+                    self.ranked = true;
+                    // show the best matches first
+                    self.scroll = 0;
+                    self.updates.request_reset_current_index();
                 }
-            }
-            if self.updates.reset_search() {
-                self.updates.dont_reset_search();
-                if !self.search_text.is_empty() {
-                    self.search_text.clear();
-                    self.search_regex = None;
-                    self.updates.request_redraw_searchbar();
-                    self.updates.request_filter_files();
+            } else {
+                self.content_search = None;
+                for entry in &mut self.dir_content {
+                    entry.passes_filter = true;
+                    entry.context = false;
+                    entry.highlight.clear();
                 }
+                self.dir_content_len = self.dir_content.len();
+            }
+            if was_ranked && !self.ranked {
+                // keep the current entry on screen
+                self.scroll = self
+                    .current_index
+                    .saturating_sub(self.last_drawn_files_height / 2);
             }
-            if self.updates.filter_files() {
-                if self.search_regex.is_none() && !self.search_text.is_empty() {
-                    self.search_regex = RegexBuilder::new(&self.search_text)
-                        .case_insensitive(true)
-                        .build()
-                        .ok();
+            // tree branch glyphs. which glyph is used depends on the entries below,
+            // so this goes through the visible entries from the bottom up.
+            // has_next[depth]: is there another visible entry at this depth (with the same parent) below?
+            let mut has_next: Vec<bool> = vec![];
+            for entry in self
+                .dir_content
+                .iter_mut()
+                .rev()
+                .filter(|e| e.passes_filter)
+            {
+                // entries sorted by score aren't a tree anymore
+                let depth = if self.ranked { 0 } else { entry.rel_depth };
+                if has_next.len() <= depth {
+                    has_next.resize(depth + 1, false);
                 }
-                self.updates.dont_filter_files();
-                self.updates.request_redraw_filelist();
-                if let Some(regex) = &self.search_regex {
-                    self.dir_content_len = 0;
-                    for entry in &mut self.dir_content {
-                        entry.passes_filter = regex.is_match(&entry.name);
-                        if entry.passes_filter {
-                            self.dir_content_len += 1;
-                        }
-                    }
+                entry.tree.clear();
+                if depth == 0 {
+                    entry.tree.push_str("- ");
                 } else {
-                    for entry in &mut self.dir_content {
-                        entry.passes_filter = true;
+                    entry.tree.push_str("  ");
+                    for next in &has_next[1..depth] {
+                        entry.tree.push_str(if *next { "│   " } else { "    " });
+                    }
+                    entry.tree.push_str(if has_next[depth] {
+                        "├── "
+                    } else {
+                        "└── "
+                    });
+                }
+                has_next[depth] = true;
+                has_next.truncate(depth + 1);
+            }
+            if !self.files_status_is_special {
+                // `More` entries are always shown, but they aren't counted as entries,
+                // and neither are directories which are only shown because of the entries inside them
+                let mut not_loaded = 0;
+                let mut more_entries = 0;
+                let mut context_entries = 0;
+                for e in &self.dir_content {
+                    if let DirContentType::More { remaining, .. } = e.more {
+                        not_loaded += remaining;
+                        more_entries += 1;
+                    }
+                    if e.context {
+                        context_entries += 1;
                     }
-                    self.dir_content_len = self.dir_content.len();
                 }
-                if !self.files_status_is_special {
-                    self.files_status = match (
-                        self.dir_content_len != self.dir_content.len(),
-                        self.dir_content.len() == 1,
-                    ) {
-                        (false, false) => format!("{} entries", self.dir_content_len),
-                        (false, true) => format!("1 entry"),
-                        (true, false) => format!(
-                            "{} of {} entries",
-                            self.dir_content_len,
-                            self.dir_content.len()
-                        ),
-                        (true, true) => format!("{} of 1 entry", self.dir_content_len),
-                    };
-                    if self.scan_files_max_depth > 0 {
-                        if let Some(v) = self.scan_files_max_depth.checked_add(1) {
-                            self.files_status.push_str(&format!(" ({v} layers)",));
-                        } else {
-                            self.files_status.push_str(&format!(" (recursive)",));
-                        }
+                let shown = self.dir_content_len - more_entries - context_entries;
+                let total = self.dir_content.len() - more_entries;
+                self.files_status = match (shown != total, total == 1) {
+                    (false, false) => format!("{shown} entries"),
+                    (false, true) => "1 entry".to_string(),
+                    (true, false) => format!("{shown} of {total} entries"),
+                    (true, true) => format!("{shown} of 1 entry"),
+                };
+                if not_loaded > 0 {
+                    self.files_status
+                        .push_str(&format!(" (+{not_loaded} not loaded)"));
+                }
+                if self.tree_mode {
+                    self.files_status.push_str(" (tree)");
+                } else if self.scan_files_max_depth > 0 {
+                    if let Some(v) = self.scan_files_max_depth.checked_add(1) {
+                        self.files_status.push_str(&format!(" ({v} layers)",));
+                    } else {
+                        self.files_status.push_str(" (recursive)");
                     }
                 }
+                if !self.show_hidden {
+                    self.files_status.push_str(" (hiding dotfiles)");
+                }
+                if self
+                    .content_search
+                    .as_ref()
+                    .is_some_and(|s| !s.results.lock().unwrap().done)
+                {
+                    self.files_status.push_str(" (searching contents...)");
+                }
             }
-            if self.updates.reset_current_index() {
-                self.updates.dont_reset_current_index();
-                self.set_current_index_to_visible(0, true);
+        }
+        if self.updates.reset_current_index() {
+            self.updates.dont_reset_current_index();
+            self.set_current_index_to_visible(0, true);
+        }
+        // load the preview of the entry under the cursor
+        if self.show_preview {
+            let current = self
+                .dir_content
+                .get(self.current_index)
+                .filter(|e| e.passes_filter && e.is_entry())
+                .map(|e| &e.path);
+            if current != self.preview.as_ref().map(|p| &p.path) {
+                self.preview = current.map(|path| Preview::start(path.clone()));
+                self.updates.request_redraw_preview();
+            } else if let Some(preview) = &self.preview {
+                if !preview.drawn && preview.lines.lock().unwrap().is_some() {
+                    self.updates.request_redraw_preview();
+                }
             }
-            // draw tui
-            if share.size.0 > 0 && share.size.1 > 0 {
-                if self.updates.clear() {
-                    self.updates.dont_clear();
-                    self.updates.request_move_cursor();
+        }
+    }
+    /// draws the parts of the tui which `updates` requests, into the pane at `share.origin`
+    fn draw(&mut self, share: &mut Share) -> io::Result<()> {
+        if share.size.0 > 0 && share.size.1 > 0 {
+            if self.updates.clear() {
+                self.updates.dont_clear();
+                self.updates.request_move_cursor();
+                if share.size == share.term_size {
                     queue!(share.stdout, terminal::Clear(terminal::ClearType::All))?;
-                }
-                if self.updates.redraw_infobar() {
-                    self.updates.dont_redraw_infobar();
-                    self.updates.request_move_cursor();
-                    let mut pathstring = share.status.clone();
-                    if share.tasks.len() > 0 {
-                        self.updates.request_redraw_infobar();
-                        for task in share.tasks.iter() {
-                            pathstring.push_str(" | ");
-                            pathstring.push_str(task.status.lock().unwrap().as_str());
-                        }
-                    }
-                    pathstring.push_str("  -  ");
-                    if share.size.0 as usize > pathstring.len() {
-                        let mut pathchars = Vec::with_capacity(self.current_dir.as_os_str().len());
-                        let mut maxlen = share.size.0 as usize - pathstring.len();
-                        for ch in self
-                            .current_dir
-                            .as_os_str()
-                            .to_string_lossy()
-                            .to_string()
-                            .chars()
-                            .rev()
-                        {
-                            if maxlen > 0 {
-                                pathchars.push(ch);
-                                maxlen -= 1;
-                            }
-                        }
-                        pathstring.extend(pathchars.into_iter().rev());
-                        pathstring.reserve_exact(maxlen as usize);
-                        for _ in 0..maxlen {
-                            pathstring.push(' ');
-                        }
+                } else {
+                    // only clear this pane
+                    let empty_line = " ".repeat(share.size.0 as usize);
+                    for y in 0..share.size.1 {
                         queue!(
                             share.stdout,
-                            cursor::MoveTo(0, 0),
-                            style::PrintStyledContent(
-                                pathstring
-                                    .green()
-                                    .underlined()
-                                    .bold()
-                                    .attribute(Attribute::Underlined)
-                            )
+                            cursor::MoveTo(share.origin.0, share.origin.1 + y),
+                            style::Print(&empty_line)
                         )?;
                     }
                 }
-                if self.updates.redraw_filebar() || self.updates.redraw_filelist() {
-                    self.updates.request_redraw_filebar();
-                    self.updates.dont_redraw_filebar();
-                    self.updates.request_move_cursor();
-                    self.last_drawn_files_height = share.size.1.saturating_sub(3) as _;
-                    let mut status = match self.scan_files_mode {
-                        ScanFilesMode::Blocking => " ".to_string(),
-                        ScanFilesMode::Threaded => " (t) ".to_string(),
-                        ScanFilesMode::Timeout(secs) => format!(" ({secs}s) "),
-                        ScanFilesMode::TimeoutThenThreaded(secs) => format!(" ({secs}s -> t) "),
-                    };
-                    status.push_str(&self.files_status);
-                    while status.len() < share.size.0 as usize {
-                        status.push(' ');
+            }
+            if self.updates.redraw_infobar() {
+                self.updates.dont_redraw_infobar();
+                self.updates.request_move_cursor();
+                let mut pathstring = share.status.clone();
+                if !share.tasks.is_empty() {
+                    self.updates.request_redraw_infobar();
+                    for task in share.tasks.iter() {
+                        pathstring.push_str(" | ");
+                        pathstring.push_str(task.status.lock().unwrap().as_str());
+                    }
+                }
+                if let Some(clipboard) = &share.clipboard {
+                    pathstring.push_str(&format!(
+                        " | {} {}",
+                        if clipboard.cut { "cut" } else { "yanked" },
+                        clipboard.entries.len()
+                    ));
+                }
+                pathstring.push_str("  -  ");
+                let status_width = width::width(&pathstring);
+                if share.size.0 as usize > status_width {
+                    let maxlen = share.size.0 as usize - status_width;
+                    // the panes of other instances are dimmed
+                    let mut path_style = share.theme.path;
+                    let mut home_style = share.theme.home;
+                    if share.pane != share.active_instance {
+                        path_style.attributes.set(Attribute::Dim);
+                        home_style.attributes.set(Attribute::Dim);
                     }
+                    let (crumbs, path_width) = self.path_bar.layout(
+                        &self.current_dir,
+                        share.home.as_deref(),
+                        share.origin.0 + status_width as u16..share.origin.0 + share.size.0,
+                        self.path_jump,
+                        path_style,
+                        home_style,
+                    );
                     queue!(
                         share.stdout,
-                        cursor::MoveTo(0, 1),
-                        style::PrintStyledContent(status.attribute(Attribute::Italic)),
+                        cursor::MoveTo(share.origin.0, share.origin.1),
+                        style::PrintStyledContent(StyledContent::new(path_style, pathstring))
                     )?;
-                    if self.updates.redraw_filelist() {
-                        self.updates.dont_redraw_filelist();
-                        self.last_files_max_scroll = self
-                            .dir_content_len
-                            .saturating_sub(self.last_drawn_files_height);
-                        let scrollbar_where = if self.last_files_max_scroll > 0 {
-                            Some(
-                                self.last_drawn_files_height.saturating_sub(1) * self.scroll
-                                    / self.last_files_max_scroll,
-                            )
-                        } else {
-                            None
-                        };
-                        let mut drawn_files = 0;
-                        for (line, entry) in self
-                            .dir_content
-                            .iter()
-                            .skip(self.scroll)
-                            .filter(|e| e.passes_filter)
-                            .take(self.last_drawn_files_height)
-                            .enumerate()
-                        {
-                            drawn_files += 1;
-                            let (mut text, mut text_charlen) = ("- ".to_string(), 2);
-                            for _ in 0..entry.rel_depth {
-                                text.push_str("  | ");
-                            }
-                            text_charlen += entry.rel_depth * 4;
-                            let endchar = if let Some(sb_where) = scrollbar_where {
-                                if line == sb_where {
-                                    '#'
-                                } else {
-                                    '|'
-                                }
+                    for crumb in crumbs {
+                        queue!(share.stdout, style::PrintStyledContent(crumb))?;
+                    }
+                    queue!(
+                        share.stdout,
+                        style::PrintStyledContent(StyledContent::new(
+                            path_style,
+                            " ".repeat(maxlen - path_width)
+                        ))
+                    )?;
+                } else {
+                    self.path_bar.clear();
+                }
+            }
+            if let (Some(index), Some(clipboard)) = (self.clipboard_view, &share.clipboard) {
+                if self.updates.redraw_filelist() {
+                    // the clipboard is drawn where the file list would be
+                    self.updates.dont_redraw_filelist();
+                    self.updates.request_redraw_filebar();
+                    let height = self.search_bar_line(share).saturating_sub(2) as usize;
+                    let mut lines = vec![format!(
+                        "Clipboard: {} entries, C {} them (Delete: remove, Tab: copy/move, any other key: close)",
+                        clipboard.entries.len(),
+                        if clipboard.cut { "moves" } else { "copies" }
+                    )];
+                    // keep the highlighted entry visible
+                    let skip = (index + 1).saturating_sub(height.saturating_sub(1));
+                    for entry in clipboard.entries.iter().skip(skip) {
+                        lines.push(format!(
+                            "{}{}",
+                            entry.parent.join(&entry.rel_path).display(),
+                            if entry.recursive { " (recursive)" } else { "" }
+                        ));
+                    }
+                    draw_lines(share, height, &lines, |i, text| match i {
+                        0 => text.bold(),
+                        _ if i - 1 + skip == index => text.reverse(),
+                        _ => text.stylize(),
+                    })?;
+                    // when the file list is drawn again, all of these lines have to be cleared
+                    self.last_drawn_files_count = height;
+                }
+            }
+            if let Some(picker) = &self.saved_picker {
+                if self.updates.redraw_filelist() {
+                    // the saved filters are drawn where the file list would be
+                    self.updates.dont_redraw_filelist();
+                    self.updates.request_redraw_filebar();
+                    let height = self.search_bar_line(share).saturating_sub(2) as usize;
+                    let mut lines = vec![
+                        "Saved filters (Enter: recall, or save as name, Tab: with selection, Delete: remove, Esc: close)".to_owned(),
+                        format!(
+                            "name: {}{}",
+                            picker.name,
+                            if picker.with_selection {
+                                format!(
+                                    " (with {} selected)",
+                                    self.dir_content.iter().filter(|e| e.selected).count()
+                                )
                             } else {
-                                ' '
-                            };
-                            let styled = match &entry.more {
-                                DirContentType::Err(e) => {
-                                    text.push_str(&entry.name);
-                                    text_charlen += entry.name_charlen;
-                                    while text_charlen + 9 > share.size.0 as usize {
-                                        text.pop();
-                                        text_charlen -= 1;
-                                    }
-                                    text.push_str(" - Err: ");
-                                    // make text_charlen 1 too large (for the endchar)
-                                    text_charlen += 9;
-                                    for ch in e.chars() {
-                                        if ch == '\n' || ch == '\r' {
-                                            continue;
-                                        }
-                                        if text_charlen >= share.size.0 as usize {
-                                            break;
-                                        }
-                                        text_charlen += 1;
-                                        text.push(ch);
-                                    }
-                                    while text_charlen < share.size.0 as _ {
-                                        text.push(' ');
-                                        text_charlen += 1;
-                                    }
-                                    text.push(endchar);
-                                    vec![text.red()]
-                                }
-                                DirContentType::File { metadata }
-                                | DirContentType::Dir { metadata }
-                                | DirContentType::Symlink { metadata } => {
-                                    let filenamelen =
-                                        share.size.0 as usize - 2 - text_charlen - entry.info.len();
-                                    if entry.name_charlen < filenamelen {
-                                        text.push_str(&entry.name);
-                                        for _ in 0..(filenamelen - entry.name_charlen) {
-                                            text.push(' ');
-                                        }
-                                    } else if entry.name_charlen == filenamelen {
-                                        text.push_str(&entry.name);
-                                    } else {
-                                        // the new length is the old length minus the combined length of the characters we want to cut off
-                                        let i = entry.name.len()
-                                            - entry
-                                                .name
-                                                .chars()
-                                                .rev()
-                                                .take(entry.name_charlen - filenamelen)
-                                                .map(|char| char.len_utf8())
-                                                .sum::<usize>();
-                                        text.push_str(&entry.name[0..i.saturating_sub(3)]);
-                                        text.push_str("...");
-                                    }
-                                    text.push_str(&entry.info);
-                                    text.push(' ');
-                                    text.push(endchar);
-                                    vec![match entry.more {
-                                        DirContentType::File { .. } => text.blue(),
-                                        DirContentType::Dir { .. } => text.yellow(),
-                                        DirContentType::Symlink { .. } => text.grey(),
-                                        DirContentType::Err { .. } => text.red(),
-                                    }]
-                                }
-                            };
-                            queue!(share.stdout, cursor::MoveToNextLine(1))?;
-                            for mut s in styled {
-                                if entry.selected {
-                                    s = s.bold();
-                                }
-                                queue!(share.stdout, style::PrintStyledContent(s))?;
+                                String::new()
                             }
+                        ),
+                    ];
+                    // keep the highlighted filter visible
+                    let skip = (picker.index + 1).saturating_sub(height.saturating_sub(2));
+                    for saved in share.saved_filters.list.iter().skip(skip) {
+                        let mut line = format!("{}: ", saved.name);
+                        for filter in saved.filters.iter().chain([&saved.search]) {
+                            line.push_str(filter);
+                            line.push(' ');
                         }
-                        let empty_lines = self.last_drawn_files_count.saturating_sub(drawn_files);
-                        self.last_drawn_files_count = drawn_files;
-                        let empty_line = " ".repeat(share.size.0 as _);
-                        for _ in 0..empty_lines {
-                            queue!(
-                                share.stdout,
-                                cursor::MoveToNextLine(1),
-                                style::PrintStyledContent(empty_line.as_str().stylize())
-                            )?;
+                        if !saved.selection.is_empty() {
+                            line.push_str(&format!("(+{} selected)", saved.selection.len()));
                         }
+                        lines.push(line);
                     }
+                    draw_lines(share, height, &lines, |i, text| match i {
+                        0 => text.bold(),
+                        1 => text.underlined(),
+                        _ if i - 2 + skip == picker.index => text.reverse(),
+                        _ => text.stylize(),
+                    })?;
+                    // when the file list is drawn again, all of these lines have to be cleared
+                    self.last_drawn_files_count = height;
                 }
-                if self.updates.redraw_searchbar() {
-                    self.updates.dont_redraw_searchbar();
-                    self.updates.request_move_cursor();
-                    let mut text = if self.search_text.len() > share.size.0 as _ {
-                        self.search_text[(self.search_text.len() - share.size.0 as usize)..]
-                            .to_string()
-                    } else {
-                        self.search_text.clone()
+            }
+            if self.help.is_some() && self.updates.redraw_filelist() {
+                // the keys are drawn where the file list would be
+                self.updates.dont_redraw_filelist();
+                self.updates.request_redraw_filebar();
+                let height = self.search_bar_line(share).saturating_sub(2) as usize;
+                if let Some(help) = &mut self.help {
+                    let keys = help.lines();
+                    help.scroll = help
+                        .scroll
+                        .min(keys.len().saturating_sub(height.saturating_sub(2)));
+                    let mut lines = vec![
+                        "Keys (type to filter, Up/Down: scroll, Esc: close)".to_owned(),
+                        format!("filter: {}", help.filter),
+                    ];
+                    lines.extend(keys.into_iter().skip(help.scroll));
+                    draw_lines(share, height, &lines, |i, text| match i {
+                        0 => text.bold(),
+                        1 => text.underlined(),
+                        // the titles of the contexts
+                        _ if !text.starts_with(' ') => text.bold(),
+                        _ => text.stylize(),
+                    })?;
+                }
+                // when the file list is drawn again, all of these lines have to be cleared
+                self.last_drawn_files_count = height;
+            }
+            if self.message_log.is_some() && self.updates.redraw_filelist() {
+                // the messages are drawn where the file list would be
+                self.updates.dont_redraw_filelist();
+                self.updates.request_redraw_filebar();
+                let height = self.search_bar_line(share).saturating_sub(2) as usize;
+                let messages = &share.messages.log;
+                let scroll = self
+                    .message_log
+                    .unwrap_or(0)
+                    .min(messages.len().saturating_sub(height.saturating_sub(1)));
+                self.message_log = Some(scroll);
+                let mut lines = vec![
+                    "Messages (newest first, Up/Down: scroll, any other key: close)".to_owned(),
+                ];
+                let mut levels = vec![];
+                for message in messages.iter().rev().skip(scroll) {
+                    lines.push(message.log_line());
+                    levels.push(message.level);
+                }
+                if messages.is_empty() {
+                    lines.push("no messages yet".to_owned());
+                }
+                draw_lines(share, height, &lines, |i, text| match i {
+                    0 => text.bold(),
+                    _ => match levels.get(i - 1) {
+                        Some(level) => text.with(level.color()),
+                        None => text.stylize(),
+                    },
+                })?;
+                // when the file list is drawn again, all of these lines have to be cleared
+                self.last_drawn_files_count = height;
+            }
+            if let Some(path) = &self.content_preview {
+                if self.updates.redraw_filelist() {
+                    // the matching lines are drawn where the file list would be
+                    self.updates.dont_redraw_filelist();
+                    self.updates.request_redraw_filebar();
+                    let height = self.search_bar_line(share).saturating_sub(2) as usize;
+                    let results = self
+                        .content_search
+                        .as_ref()
+                        .map(|s| s.results.lock().unwrap());
+                    let matches = results.as_ref().and_then(|r| r.files.get(path));
+                    let mut lines = vec![];
+                    if let Some(matches) = matches {
+                        lines.push(format!(
+                            "{} ({} matching line{}, press any key to close)",
+                            path.strip_prefix(&self.current_dir)
+                                .unwrap_or(path)
+                                .display(),
+                            matches.count,
+                            if matches.count == 1 { "" } else { "s" }
+                        ));
+                        let nr_width = matches
+                            .lines
+                            .last()
+                            .map_or(0, |(nr, _)| nr.to_string().len());
+                        for (nr, line) in &matches.lines {
+                            lines.push(format!("{nr:>nr_width$}: {}", line.replace('\t', "    ")));
+                        }
+                        if matches.count > matches.lines.len() {
+                            lines.push(format!(
+                                "... and {} more",
+                                matches.count - matches.lines.len()
+                            ));
+                        }
+                    }
+                    draw_lines(share, height, &lines, |i, text| {
+                        if i == 0 {
+                            text.bold()
+                        } else {
+                            text.stylize()
+                        }
+                    })?;
+                    // when the file list is drawn again, all of these lines have to be cleared
+                    self.last_drawn_files_count = height;
+                }
+            }
+            if self.updates.redraw_filebar() || self.updates.redraw_filelist() {
+                self.updates.request_redraw_filebar();
+                self.updates.dont_redraw_filebar();
+                self.updates.request_move_cursor();
+                self.last_drawn_files_height = self.search_bar_line(share).saturating_sub(2) as _;
+                let mut status = match self.scan_files_mode {
+                    ScanFilesMode::Blocking => " ".to_string(),
+                    ScanFilesMode::Threaded => " (t) ".to_string(),
+                    ScanFilesMode::Timeout(secs) => format!(" ({secs}s) "),
+                    ScanFilesMode::TimeoutThenThreaded(secs) => format!(" ({secs}s -> t) "),
+                };
+                // the active filter syntax
+                status.push_str(&format!(
+                    "[{}{}{}] ",
+                    self.filter_syntax
+                        .apply_prefix(
+                            self.search_text
+                                .strip_prefix('!')
+                                .unwrap_or(&self.search_text)
+                        )
+                        .0,
+                    if self.filter_paths { ", paths" } else { "" },
+                    if self.filter_ancestors {
+                        ", ancestors"
+                    } else {
+                        ""
+                    },
+                ));
+                status.push_str(&self.files_status);
+                let (selected, size) = self.selection_size(share);
+                if selected > 0 {
+                    status.push_str(&format!(
+                        " | {selected} selected ({})",
+                        scan::format_size(size)
+                    ));
+                }
+                if self.mark.is_some() {
+                    status.push_str(" | mark set");
+                }
+                // in columns and rows, anything longer would be drawn into the next pane
+                let status = width::escape_control(&status);
+                let (status, status_width) = width::truncate(&status, share.size.0 as usize);
+                let mut status = status.to_owned();
+                for _ in status_width..share.size.0 as usize {
+                    status.push(' ');
+                }
+                queue!(
+                    share.stdout,
+                    cursor::MoveTo(share.origin.0, share.origin.1 + 1),
+                    style::PrintStyledContent(status.attribute(Attribute::Italic)),
+                )?;
+                if self.updates.redraw_filelist() {
+                    self.updates.dont_redraw_filelist();
+                    self.updates.request_redraw_preview();
+                    // the preview pane is drawn to the right of the file list
+                    let width = (share.size.0 - self.preview_width(share)) as usize;
+                    self.last_files_max_scroll = self
+                        .dir_content_len
+                        .saturating_sub(self.last_drawn_files_height);
+                    let scrollbar_where = (self.last_drawn_files_height.saturating_sub(1)
+                        * self.scroll)
+                        .checked_div(self.last_files_max_scroll);
+                    // read the metadata of all entries which are about to be drawn.
+                    // this may make the info table wider, but all visible entries are drawn after this anyway.
+                    for entry in self
+                        .dir_content
+                        .iter_mut()
+                        .skip(self.scroll)
+                        .filter(|e| e.passes_filter)
+                        .take(self.last_drawn_files_height)
+                    {
+                        entry.load(&share.info_what, &mut self.info_widths);
+                    }
+                    // the number of matching lines is shown as the first info column
+                    let content_results = self
+                        .content_search
+                        .as_ref()
+                        .map(|s| s.results.lock().unwrap());
+                    let count_width = content_results.as_ref().map_or(0, |r| {
+                        r.files
+                            .values()
+                            .map(|m| m.count.to_string().len())
+                            .max()
+                            .unwrap_or(1)
+                    });
+                    let mut drawn_files = 0;
+                    for (line, entry) in self
+                        .dir_content
+                        .iter()
+                        .skip(self.scroll)
+                        .filter(|e| e.passes_filter)
+                        .take(self.last_drawn_files_height)
+                        .enumerate()
+                    {
+                        drawn_files += 1;
+                        let mut text = entry.tree.clone();
+                        let mut text_width = width::width(&text);
+                        let endchar = if let Some(sb_where) = scrollbar_where {
+                            if line == sb_where {
+                                '#'
+                            } else {
+                                '|'
+                            }
+                        } else {
+                            ' '
+                        };
+                        let styled = match &entry.more {
+                            DirContentType::Err(e) => {
+                                let (name, name_width) = width::truncate(
+                                    &entry.name,
+                                    width.saturating_sub(text_width + 9),
+                                );
+                                text.push_str(name);
+                                text.push_str(" - Err: ");
+                                // make text_width 1 too large (for the endchar)
+                                text_width += name_width + 9;
+                                let e: String =
+                                    e.chars().filter(|ch| *ch != '\n' && *ch != '\r').collect();
+                                let e = width::escape_control(&e);
+                                let (e, e_width) =
+                                    width::truncate(&e, width.saturating_sub(text_width));
+                                text.push_str(e);
+                                text_width += e_width;
+                                for _ in text_width..width {
+                                    text.push(' ');
+                                }
+                                text.push(endchar);
+                                vec![StyledContent::new(share.theme.error, text)]
+                            }
+                            DirContentType::Pending
+                            | DirContentType::More { .. }
+                            | DirContentType::File { .. }
+                            | DirContentType::Dir { .. }
+                            | DirContentType::Symlink { .. } => {
+                                let mut info = entry.info_aligned(&self.info_widths);
+                                if let Some(results) = &content_results {
+                                    let count = results
+                                        .files
+                                        .get(&entry.path)
+                                        .map(|m| m.count.to_string())
+                                        .unwrap_or_default();
+                                    info = format!(" | {count:>count_width$}{info}");
+                                }
+                                let marked_name;
+                                let (name, name_width) = if let Some(reason) = entry.skipped {
+                                    marked_name = format!("{} [{reason}]", entry.name);
+                                    (&marked_name, width::width(&marked_name))
+                                } else {
+                                    (&entry.name, entry.name_width)
+                                };
+                                let filenamelen = width.saturating_sub(2 + text_width + info.len());
+                                // how many chars of the name are visible
+                                let mut name_shown = usize::MAX;
+                                if name_width <= filenamelen {
+                                    text.push_str(name);
+                                    for _ in name_width..filenamelen {
+                                        text.push(' ');
+                                    }
+                                } else {
+                                    // wide characters may leave a column free, which is filled after the "..."
+                                    let (shown, shown_width) =
+                                        width::truncate(name, filenamelen.saturating_sub(3));
+                                    name_shown = shown.chars().count();
+                                    text.push_str(shown);
+                                    text.push_str(&"..."[..filenamelen.min(3)]);
+                                    for _ in shown_width + 3..filenamelen {
+                                        text.push(' ');
+                                    }
+                                }
+                                text.push_str(&info);
+                                text.push(' ');
+                                text.push(endchar);
+                                let style = share.theme.entry_style(&entry.name, &entry.more);
+                                let color = |text: String| match entry.more {
+                                    // shown because of a match inside this directory
+                                    _ if entry.context => text.dark_grey(),
+                                    DirContentType::Pending => text.stylize(),
+                                    DirContentType::More { .. } => text.dark_grey().italic(),
+                                    _ => StyledContent::new(style, text),
+                                };
+                                // the chars which matched the filter are underlined
+                                let tree_chars = entry.tree.chars().count();
+                                let highlight = entry
+                                    .highlight
+                                    .iter()
+                                    .filter(|i| **i < name_shown)
+                                    .map(|i| i + tree_chars);
+                                split_highlighted(text, highlight)
+                                    .into_iter()
+                                    .map(|(text, highlighted)| {
+                                        if highlighted {
+                                            color(text).underlined()
+                                        } else {
+                                            color(text)
+                                        }
+                                    })
+                                    .collect()
+                            }
+                        };
+                        queue!(
+                            share.stdout,
+                            cursor::MoveTo(share.origin.0, share.origin.1 + 2 + line as u16)
+                        )?;
+                        let marked = self.mark.as_ref() == Some(&entry.path);
+                        for mut s in styled {
+                            if entry.selected {
+                                s = s.bold();
+                            }
+                            if marked {
+                                s = s.reverse();
+                            }
+                            queue!(share.stdout, style::PrintStyledContent(s))?;
+                        }
+                    }
+                    let empty_lines = self.last_drawn_files_count.saturating_sub(drawn_files);
+                    self.last_drawn_files_count = drawn_files;
+                    let empty_line = " ".repeat(width);
+                    for i in drawn_files..drawn_files + empty_lines {
+                        queue!(
+                            share.stdout,
+                            cursor::MoveTo(share.origin.0, share.origin.1 + 2 + i as u16),
+                            style::PrintStyledContent(empty_line.as_str().stylize())
+                        )?;
+                    }
+                }
+            }
+            if self.updates.redraw_preview() {
+                self.updates.dont_redraw_preview();
+                let pane_width = self.preview_width(share) as usize;
+                // the lists drawn instead of the file list cover the preview pane
+                let covered = self.content_preview.is_some()
+                    || self.saved_picker.is_some()
+                    || self.clipboard_view.is_some()
+                    || self.help.is_some()
+                    || self.message_log.is_some();
+                if pane_width > 0 && !covered {
+                    self.updates.request_move_cursor();
+                    let x = share.size.0 - pane_width as u16;
+                    let lines = match &mut self.preview {
+                        Some(preview) => {
+                            let lines = preview.lines.lock().unwrap().clone();
+                            preview.drawn = lines.is_some();
+                            lines.unwrap_or_else(|| vec![vec![("loading...".to_owned(), None)]])
+                        }
+                        None => vec![],
                     };
-                    while text.len() < share.size.0 as _ {
+                    let text_width = pane_width - 1;
+                    for i in 0..self.last_drawn_files_height {
+                        queue!(
+                            share.stdout,
+                            cursor::MoveTo(share.origin.0 + x, share.origin.1 + 2 + i as u16),
+                            style::PrintStyledContent("│".dark_grey())
+                        )?;
+                        let spans = lines.get(i).map_or(&[][..], |v| v.as_slice());
+                        let line_width: usize = spans.iter().map(|(t, _)| width::width(t)).sum();
+                        // like names in the file list, lines which don't fit end with "..."
+                        let mut left = if line_width > text_width {
+                            text_width.saturating_sub(3)
+                        } else {
+                            text_width
+                        };
+                        for (text, color) in spans {
+                            let (shown, shown_width) = width::truncate(text, left);
+                            left -= shown_width;
+                            let mut styled = shown.to_owned().stylize();
+                            if let Some(color) = color {
+                                styled = styled.with(*color);
+                            }
+                            queue!(share.stdout, style::PrintStyledContent(styled))?;
+                            if shown.len() < text.len() {
+                                break;
+                            }
+                        }
+                        // wide characters may leave columns free, which are filled after the "..."
+                        let shown = if line_width > text_width {
+                            let dots = &"..."[..text_width.min(3)];
+                            queue!(share.stdout, style::Print(dots))?;
+                            text_width - left
+                        } else {
+                            line_width
+                        };
+                        queue!(share.stdout, style::Print(" ".repeat(text_width - shown)))?;
+                    }
+                }
+            }
+            if self.updates.redraw_searchbar() {
+                self.updates.dont_redraw_searchbar();
+                self.updates.request_move_cursor();
+                let line = self.search_bar_line(share);
+                queue!(
+                    share.stdout,
+                    cursor::MoveTo(share.origin.0, share.origin.1 + line)
+                )?;
+                // the filters on the stack, as chips in front of the search text
+                let mut width = share.size.0 as usize;
+                for (i, filter) in self.filter_stack.iter().enumerate() {
+                    let chip = format!("[{}] ", filter.label());
+                    let chip = width::escape_control(&chip);
+                    let (chip, chip_width) = width::truncate(&chip, width);
+                    width -= chip_width;
+                    let mut chip = chip.to_owned().stylize();
+                    if filter.negated {
+                        chip = chip.red();
+                    }
+                    if self.selected_filter == Some(i) {
+                        chip = chip.reverse();
+                    }
+                    queue!(share.stdout, style::PrintStyledContent(chip))?;
+                }
+                // if the text is too long, only its end is shown
+                let text = self.search_bar_text(share);
+                let text = width::escape_control(&text);
+                let (text, text_width) = width::truncate_start(&text, width);
+                let mut text = text.to_owned();
+                for _ in text_width..width {
+                    text.push(' ');
+                }
+                queue!(
+                    share.stdout,
+                    style::PrintStyledContent(if self.search_error.is_some() {
+                        text.underlined().red()
+                    } else {
+                        text.underlined()
+                    })
+                )?;
+                if let Some(e) = &self.search_error {
+                    let e = e.to_string();
+                    let e = width::escape_control(&e);
+                    let (e, e_width) = width::truncate(&e, share.size.0 as usize);
+                    let mut text = e.to_owned();
+                    for _ in e_width..share.size.0 as usize {
+                        text.push(' ');
+                    }
+                    queue!(
+                        share.stdout,
+                        cursor::MoveTo(share.origin.0, share.origin.1 + line + 1),
+                        style::PrintStyledContent(text.red())
+                    )?;
+                }
+                if let Some(message) = share.messages.current().filter(|_| self.message_line) {
+                    let text = format!("{}: {}", message.level.label(), message.text);
+                    let text = width::escape_control(&text);
+                    let (text, text_width) = width::truncate(&text, share.size.0 as usize);
+                    let mut text = text.to_owned();
+                    for _ in text_width..share.size.0 as usize {
                         text.push(' ');
                     }
                     queue!(
                         share.stdout,
-                        cursor::MoveTo(0, share.size.1 - 1),
-                        style::PrintStyledContent(text.underlined())
+                        cursor::MoveTo(share.origin.0, share.origin.1 + share.size.1 - 1),
+                        style::PrintStyledContent(text.with(message.level.color()))
                     )?;
                 }
-                if self.updates.move_cursor() {
-                    self.updates.dont_move_cursor();
-                    match self.focus {
-                        Focus::Files => {
-                            if self
+            }
+            if self.updates.move_cursor() {
+                self.updates.dont_move_cursor();
+                match self.focus {
+                    Focus::Files => {
+                        if self
+                            .dir_content
+                            .get(self.current_index)
+                            .is_some_and(|e| e.passes_filter)
+                        {
+                            let height = self
                                 .dir_content
-                                .get(self.current_index)
-                                .is_some_and(|e| e.passes_filter)
-                            {
-                                let height = self
-                                    .dir_content
-                                    .iter()
-                                    .skip(self.scroll)
-                                    .take(self.current_index.saturating_sub(self.scroll))
-                                    .filter(|e| e.passes_filter)
-                                    .count();
-                                if height < self.last_drawn_files_height {
-                                    queue!(share.stdout, cursor::MoveTo(0, 2 + height as u16))?;
-                                } else {
-                                    queue!(share.stdout, cursor::MoveTo(0, 1))?;
-                                }
+                                .iter()
+                                .skip(self.scroll)
+                                .take(self.current_index.saturating_sub(self.scroll))
+                                .filter(|e| e.passes_filter)
+                                .count();
+                            if height < self.last_drawn_files_height {
+                                queue!(
+                                    share.stdout,
+                                    cursor::MoveTo(
+                                        share.origin.0,
+                                        share.origin.1 + 2 + height as u16
+                                    )
+                                )?;
                             } else {
-                                queue!(share.stdout, cursor::MoveTo(0, 1))?;
+                                queue!(
+                                    share.stdout,
+                                    cursor::MoveTo(share.origin.0, share.origin.1 + 1)
+                                )?;
                             }
-                        }
-                        Focus::SearchBar => {
+                        } else {
                             queue!(
                                 share.stdout,
-                                cursor::MoveTo(self.search_text.len() as _, share.size.1 - 1)
+                                cursor::MoveTo(share.origin.0, share.origin.1 + 1)
                             )?;
                         }
                     }
+                    Focus::SearchBar | Focus::SelectBar { .. } => {
+                        let line = self.search_bar_line(share);
+                        let x = self
+                            .filter_stack
+                            .iter()
+                            .map(|f| width::width(&width::escape_control(&f.label())) + 3)
+                            .sum::<usize>()
+                            + width::width(&width::escape_control(&self.search_bar_text(share)));
+                        let x = x.min((share.size.0 as usize).saturating_sub(1));
+                        queue!(
+                            share.stdout,
+                            cursor::MoveTo(share.origin.0 + x as u16, share.origin.1 + line)
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    pub fn run(
+        &mut self,
+        share: &mut Share,
+        before: &mut [TuiFile],
+        after: &mut [TuiFile],
+    ) -> io::Result<AppCmd> {
+        loop {
+            if let Some(rescan) = share.check_bgtasks() {
+                if rescan {
+                    return Ok(AppCmd::RescanFiles);
                 }
             }
-            // end of draw
+            // the other panes are drawn first, so the cursor ends up in this one
+            if share.layout != Layout::Single {
+                let active = share.active_instance;
+                let others = before.iter_mut().enumerate().chain(
+                    after
+                        .iter_mut()
+                        .enumerate()
+                        .map(|(i, v)| (active + 1 + i, v)),
+                );
+                for (index, other) in others {
+                    // only the active instance shows the message line
+                    if other.message_line {
+                        other.message_line = false;
+                        other.updates.request_clear();
+                        other.updates.request_redraw();
+                    }
+                    other.update(share);
+                    if other.updates != 0 {
+                        share.set_pane(index);
+                        other.draw(share)?;
+                        self.updates.request_move_cursor();
+                    }
+                }
+                share.set_pane(active);
+            }
+            self.update(share);
+            share.messages.expire();
+            if share.messages.current().is_some() != self.message_line {
+                // the message line moves the search bar and takes space from the file list
+                self.message_line = !self.message_line;
+                share.messages.take_changed();
+                self.updates.request_clear();
+                self.updates.request_redraw();
+            } else if share.messages.take_changed() {
+                self.updates.request_redraw_searchbar();
+            }
+            if share.tab_bar.is_some() {
+                let tabs = before
+                    .iter()
+                    .map(|v| Tab::new(v, false))
+                    .chain([Tab::new(self, true)])
+                    .chain(after.iter().map(|v| Tab::new(v, false)))
+                    .collect();
+                if tabs::draw(share, tabs)? {
+                    self.updates.request_move_cursor();
+                }
+            }
+            self.draw(share)?;
             share.stdout.flush()?;
             // events
             if poll(Duration::from_millis(100))? {
                 match read()? {
                     Event::FocusGained => {}
                     Event::FocusLost => {}
-                    Event::Mouse(e) => match (e.kind, e.column, e.row, e.modifiers) {
-                        _ => {}
-                    },
-                    Event::Key(e) => match (&self.focus, e.code) {
-                        // - - - Global - - -
-                        // Ctrl+C/D -> Quit
-                        (_, KeyCode::Char('c' | 'd')) if e.modifiers == KeyModifiers::CONTROL => {
-                            return Ok(AppCmd::Quit);
+                    Event::Mouse(e)
+                        if matches!(self.focus, Focus::Files)
+                            && self.content_preview.is_none()
+                            && self.clipboard_view.is_none()
+                            && self.saved_picker.is_none()
+                            && self.help.is_none()
+                            && self.message_log.is_none() =>
+                    {
+                        if let Some(cmd) = self.mouse_event(e, share) {
+                            return Ok(cmd);
                         }
-                        // Ctrl+Left/H -> Close
-                        (_, KeyCode::Left | KeyCode::Char('h'))
-                            if e.modifiers == KeyModifiers::CONTROL =>
+                    }
+                    Event::Mouse(_) => {}
+                    // any key closes the preview of the matching lines
+                    Event::Key(_) if self.content_preview.is_some() => {
+                        self.content_preview = None;
+                        self.updates.request_redraw_filelist();
+                    }
+                    Event::Key(e) if self.message_log.is_some() => {
+                        if matches!(e.code, KeyCode::Char('c' | 'd'))
+                            && e.modifiers == KeyModifiers::CONTROL
                         {
-                            return Ok(AppCmd::CloseInstance);
+                            return Ok(AppCmd::Quit);
                         }
-                        // Ctrl+Right/L -> Duplicate
-                        (_, KeyCode::Right | KeyCode::Char('l'))
-                            if e.modifiers == KeyModifiers::CONTROL =>
+                        self.message_log_key(e);
+                    }
+                    Event::Key(e) if self.help.is_some() => {
+                        if matches!(e.code, KeyCode::Char('c' | 'd'))
+                            && e.modifiers == KeyModifiers::CONTROL
                         {
-                            return Ok(AppCmd::AddInstance(self.clone()));
+                            return Ok(AppCmd::Quit);
                         }
-                        // Ctrl+Up/K -> Prev
-                        (_, KeyCode::Up | KeyCode::Char('k'))
-                            if e.modifiers == KeyModifiers::CONTROL =>
+                        self.help_key(e);
+                    }
+                    Event::Key(e) if self.clipboard_view.is_some() => {
+                        if matches!(e.code, KeyCode::Char('c' | 'd'))
+                            && e.modifiers == KeyModifiers::CONTROL
                         {
-                            return Ok(AppCmd::PrevInstance);
+                            return Ok(AppCmd::Quit);
                         }
-                        // Ctrl+Down/J -> Next
-                        (_, KeyCode::Down | KeyCode::Char('j'))
-                            if e.modifiers == KeyModifiers::CONTROL =>
+                        self.clipboard_view_key(e, share);
+                    }
+                    Event::Key(e) if self.saved_picker.is_some() => {
+                        if matches!(e.code, KeyCode::Char('c' | 'd'))
+                            && e.modifiers == KeyModifiers::CONTROL
                         {
-                            return Ok(AppCmd::NextInstance);
-                        }
-                        // - - - Files - - -
-                        // Down/J -> Down
-                        (Focus::Files, KeyCode::Down | KeyCode::Char('j')) => {
-                            self.set_current_index_to_visible(self.current_index + 1, true)
-                        }
-                        // Up/K -> Up
-                        (Focus::Files, KeyCode::Up | KeyCode::Char('k')) => {
-                            if self.current_index > 0 {
-                                self.set_current_index_to_visible(self.current_index - 1, false)
-                            }
-                        }
-                        // Left/H -> Leave Directory
-                        (Focus::Files, KeyCode::Left | KeyCode::Char('h')) => {
-                            // leave directory
-                            if let Some(this_dir) = self
-                                .current_dir
-                                .file_name()
-                                .map(|name| name.to_string_lossy().into_owned())
-                            {
-                                self.current_dir.pop();
-                                self.updates.request_redraw_infobar();
-                                self.request_rescan_files_then_select_by_name(this_dir);
-                            }
-                        }
-                        // Right/L -> Enter Directory
-                        (Focus::Files, KeyCode::Right | KeyCode::Char('l')) => {
-                            // descend into directory
-                            if let Some(entry) = self.dir_content.get(self.current_index) {
-                                self.current_dir = entry.path.clone();
-                                self.updates = u32::MAX;
-                            }
-                        }
-                        // A -> Select All
-                        (Focus::Files, KeyCode::Char('a')) => {
-                            self.updates.request_redraw_filelist();
-                            for e in &mut self.dir_content {
-                                if e.passes_filter {
-                                    e.selected = !e.selected;
-                                }
-                            }
-                        }
-                        // S -> Toggle Select
-                        (Focus::Files, KeyCode::Char('s')) => {
-                            self.updates.request_redraw_filelist();
-                            if let Some(e) = self.dir_content.get_mut(self.current_index) {
-                                e.selected = !e.selected;
-                            }
-                        }
-                        // D -> Deselect All
-                        (Focus::Files, KeyCode::Char('d')) => {
-                            self.updates.request_redraw_filelist();
-                            for e in &mut self.dir_content {
-                                if e.passes_filter {
-                                    e.selected = false;
-                                }
-                            }
-                        }
-                        // Search
-                        (Focus::Files, KeyCode::Char('f' | '/')) => {
-                            self.focus = Focus::SearchBar;
-                            self.updates.request_move_cursor();
-                        }
-                        // M -> toggle threaded mode based on searchbar
-                        (Focus::Files, KeyCode::Char('m')) => {
-                            self.updates.request_reset_search();
-                            self.updates.request_redraw_filebar();
-                            if self.search_text == "b" {
-                                self.scan_files_mode = ScanFilesMode::Blocking;
-                            } else if self.search_text == "t" {
-                                self.scan_files_mode = ScanFilesMode::Threaded;
-                            } else if self.search_text.starts_with("b") {
-                                if let Ok(timeout) = self.search_text[1..].parse() {
-                                    self.scan_files_mode = ScanFilesMode::Timeout(timeout);
-                                }
-                            } else if self.search_text.starts_with("t") {
-                                if let Ok(timeout) = self.search_text[1..].parse() {
-                                    self.scan_files_mode =
-                                        ScanFilesMode::TimeoutThenThreaded(timeout);
-                                }
-                            }
-                        }
-                        // N -> New Directory based on search bar
-                        (Focus::Files, KeyCode::Char('n')) => {
-                            let dir = self.current_dir.join(&self.search_text);
-                            if fs::create_dir_all(&dir).is_ok() {
-                                self.updates.request_reset_search();
-                                self.current_dir = dir;
-                                self.updates.request_redraw_infobar();
-                            }
-                            self.updates.request_rescan_files();
-                        }
-                        // C -> Copy
-                        (Focus::Files, KeyCode::Char('c')) => {
-                            return Ok(AppCmd::CopyTo(self.current_dir.clone()));
-                        }
-                        // R -> Remove
-                        (Focus::Files, KeyCode::Char('r')) => {
-                            let paths = self
-                                .dir_content
-                                .iter()
-                                .rev()
-                                .filter(|e| e.selected)
-                                .map(|e| e.path.clone())
-                                .collect();
-                            self.updates.request_redraw_infobar();
-                            tasks::task_del(paths, share);
+                            return Ok(AppCmd::Quit);
                         }
-                        // P -> Permissions
-                        (Focus::Files, KeyCode::Char('p')) => {
-                            self.updates.request_reset_search();
-                            if let Ok(mode) = u32::from_str_radix(&self.search_text, 8) {
-                                let paths = self
-                                    .dir_content
-                                    .iter()
-                                    .rev()
-                                    .filter(|e| e.selected)
-                                    .map(|e| e.path.clone())
-                                    .collect();
-                                self.updates.request_redraw_infobar();
-                                tasks::task_chmod(paths, mode, share);
+                        self.saved_picker_key(e, share);
+                    }
+                    Event::Key(e) => {
+                        if let Some(binding) = keymap::find(&e, self) {
+                            if let Some(cmd) = self.key_action(binding.action, e, share)? {
+                                return Ok(cmd);
                             }
                         }
-                        // O -> Owner (and group)
-                        (Focus::Files, KeyCode::Char('o')) => {
-                            self.updates.request_reset_search();
-                            // TODO!
-                        }
-                        // Query files (Edit doesn't do this automatically, but running a shell does)
-                        (Focus::Files, KeyCode::Char('q')) => {
-                            return Ok(AppCmd::RescanFiles);
+                    }
+                    Event::Paste(_e) => {}
+                    Event::Resize(w, h) => {
+                        share.term_size = (w, h);
+                        return Ok(AppCmd::Redraw);
+                    }
+                }
+            }
+        }
+    }
+
+    /// does what the key `e` is bound to in the keymap
+    fn key_action(
+        &mut self,
+        action: Action,
+        e: KeyEvent,
+        share: &mut Share,
+    ) -> io::Result<Option<AppCmd>> {
+        match action {
+            // - - - Global - - -
+            // Ctrl+C/D -> Quit
+            Action::Quit => {
+                return Ok(Some(AppCmd::Quit));
+            }
+            // Ctrl+Left/H -> Close
+            Action::CloseInstance => {
+                return Ok(Some(AppCmd::CloseInstance));
+            }
+            // Ctrl+Right/L -> Duplicate
+            Action::DuplicateInstance => {
+                return Ok(Some(AppCmd::AddInstance(Box::new(self.clone()))));
+            }
+            // Ctrl+Up/K -> Prev
+            Action::PrevInstance => {
+                return Ok(Some(AppCmd::PrevInstance));
+            }
+            // Ctrl+Down/J -> Next
+            Action::NextInstance => {
+                return Ok(Some(AppCmd::NextInstance));
+            }
+            // Alt+0-9 -> Go to tab
+            Action::GoToTab => {
+                if let KeyCode::Char(ch) = e.code {
+                    return Ok(Some(AppCmd::FocusInstance(ch as usize - '0' as usize)));
+                }
+            }
+            // Alt+Left/H -> Move tab left
+            Action::MoveTabLeft => {
+                return Ok(Some(AppCmd::MoveInstanceLeft));
+            }
+            // Alt+Right/L -> Move tab right
+            Action::MoveTabRight => {
+                return Ok(Some(AppCmd::MoveInstanceRight));
+            }
+            // F1 or ? -> show the keys
+            Action::Help => {
+                self.help = Some(HelpView {
+                    context: Context::of(self),
+                    filter: String::new(),
+                    scroll: 0,
+                });
+                self.updates.request_redraw_filelist();
+            }
+            // - - - Files - - -
+            // ! -> Message log
+            Action::MessageLog => {
+                share.messages.dismiss();
+                self.message_log = Some(0);
+                self.updates.request_redraw_filelist();
+            }
+            // ` -> number the directories in the path bar, to go to one of them with 0-9
+            Action::PathJump => {
+                self.path_jump = true;
+                self.updates.request_redraw_infobar();
+            }
+            // Down/J -> Down
+            Action::Down => self.set_current_index_to_visible(self.current_index + 1, true),
+            // Up/K -> Up
+            Action::Up if self.current_index > 0 => {
+                self.set_current_index_to_visible(self.current_index - 1, false)
+            }
+            // Left/H in tree mode -> Collapse directory or go to parent entry
+            Action::Collapse => {
+                self.restore_scan_order();
+                let entry = &self.dir_content[self.current_index];
+                if entry.is_entry() && self.expanded.contains(&entry.path) {
+                    self.tree_collapse(self.current_index);
+                } else if let Some(parent) = self.dir_content[..self.current_index]
+                    .iter()
+                    .rposition(|e| e.rel_depth < entry.rel_depth)
+                {
+                    self.set_current_index_to_visible(parent, false);
+                }
+            }
+            // Left/H -> Leave Directory
+            Action::Parent => {
+                // leave directory
+                if let Some(this_dir) = self
+                    .current_dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                {
+                    self.current_dir.pop();
+                    self.updates.request_redraw_infobar();
+                    self.request_rescan_files_then_select_by_name(this_dir);
+                }
+            }
+            // Right/L -> Enter Directory
+            Action::Enter => self.enter_current(share),
+            // A -> Select All
+            Action::ToggleAll => {
+                self.updates.request_redraw_filelist();
+                self.selection_changed();
+                for e in &mut self.dir_content {
+                    if e.passes_filter && e.is_entry() {
+                        e.selected = !e.selected;
+                    }
+                }
+            }
+            // S -> Toggle Select
+            Action::ToggleCurrent => {
+                self.updates.request_redraw_filelist();
+                self.selection_changed();
+                if let Some(e) = self.dir_content.get_mut(self.current_index) {
+                    if e.is_entry() {
+                        e.selected = !e.selected;
+                    }
+                }
+            }
+            // + -> select all entries matching a pattern, - -> deselect them
+            Action::SelectMatching | Action::DeselectMatching => {
+                self.focus = Focus::SelectBar {
+                    deselect: action == Action::DeselectMatching,
+                };
+                self.select_text.clear();
+                self.history_index = None;
+                self.updates.request_redraw_searchbar();
+            }
+            // * -> invert the selection of all listed entries
+            Action::InvertSelection => {
+                self.updates.request_redraw_filelist();
+                self.selection_changed();
+                for e in &mut self.dir_content {
+                    if e.is_entry() {
+                        e.selected = !e.selected;
+                    }
+                }
+            }
+            // X -> select all files with the same eXtension as the current one
+            Action::SelectExtension => {
+                if let Some(current) = self
+                    .dir_content
+                    .get(self.current_index)
+                    .filter(|e| e.is_entry() && !e.name.ends_with('/'))
+                {
+                    let ext = current.path.extension().map(|v| v.to_ascii_lowercase());
+                    for e in &mut self.dir_content {
+                        if e.is_entry()
+                            && !e.name.ends_with('/')
+                            && e.path.extension().map(|v| v.to_ascii_lowercase()) == ext
+                        {
+                            e.selected = true;
                         }
-                        // W -> Shell (write a command, also it's they key above S for shell, and it's near E for Edit)
-                        (Focus::Files, KeyCode::Char('w')) => {
-                            Self::term_reset(share)?;
-                            if let Some(file) = self.dir_content.get(self.current_index) {
-                                eprintln!(
-                                    "Exit shell to return to TUIFILE. Selected file: {}",
-                                    file.path.to_string_lossy()
-                                );
+                    }
+                    self.updates.request_redraw_filelist();
+                    self.selection_changed();
+                }
+            }
+            // Space -> set the mark, or select the visible entries between the mark and this one
+            Action::Mark => {
+                if let Some(current) = self
+                    .dir_content
+                    .get(self.current_index)
+                    .filter(|e| e.is_entry())
+                {
+                    let mark = self
+                        .mark
+                        .take()
+                        .and_then(|mark| self.dir_content.iter().position(|e| e.path == mark));
+                    match mark {
+                        // Space on the marked entry removes the mark
+                        Some(i) if i == self.current_index => {}
+                        Some(i) => {
+                            let (start, end) = if i < self.current_index {
+                                (i, self.current_index)
                             } else {
-                                eprintln!("Exit shell to return to TUIFILE.");
-                            }
-                            match Command::new(&share.shell_command)
-                                .current_dir(&self.current_dir)
-                                .status()
-                            {
-                                Ok(s) => {
-                                    if !s.success() {
-                                        std::thread::sleep(Duration::from_secs(2));
-                                    }
-                                }
-                                Err(e) => {
-                                    eprintln!("Error running command {}: {e}", share.shell_command);
-                                    std::thread::sleep(Duration::from_secs(2));
+                                (self.current_index, i)
+                            };
+                            for e in &mut self.dir_content[start..=end] {
+                                if e.passes_filter && e.is_entry() {
+                                    e.selected = true;
                                 }
                             }
-                            self.term_setup(share)?;
-                            return Ok(AppCmd::RescanFiles);
                         }
-                        // E -> Edit
-                        (Focus::Files, KeyCode::Char('e')) => {
-                            Self::term_reset(share)?;
-                            let entry_path = self
-                                .dir_content
-                                .get(self.current_index)
-                                .map(|v| &v.path)
-                                .unwrap_or(&self.current_dir);
-                            match Command::new(&share.editor_command)
-                                .arg(&entry_path)
-                                .current_dir(&self.current_dir)
-                                .status()
-                            {
-                                Ok(s) => {
-                                    if !s.success() {
-                                        std::thread::sleep(Duration::from_secs(2));
-                                    }
-                                }
-                                Err(e) => {
-                                    eprintln!(
-                                        "Error running command {}: {e}",
-                                        share.editor_command
-                                    );
-                                    std::thread::sleep(Duration::from_secs(2));
-                                }
+                        // the marked entry may not be listed anymore
+                        None => self.mark = Some(current.path.clone()),
+                    }
+                    self.updates.request_redraw_filelist();
+                    self.selection_changed();
+                }
+            }
+            // D -> Deselect All
+            Action::DeselectAll => {
+                self.updates.request_redraw_filelist();
+                self.selection_changed();
+                for e in &mut self.dir_content {
+                    if e.passes_filter {
+                        e.selected = false;
+                    }
+                }
+            }
+            // Search
+            Action::Search => {
+                self.focus = Focus::SearchBar;
+                self.updates.request_move_cursor();
+            }
+            // T -> toggle tree mode
+            Action::TreeMode => {
+                self.tree_mode = !self.tree_mode;
+                share.messages.info(if self.tree_mode {
+                    "tree mode on"
+                } else {
+                    "tree mode off"
+                });
+                self.request_rescan_files_then_select_current_again();
+            }
+            // . -> toggle hidden files
+            Action::ToggleHidden => {
+                self.show_hidden = !self.show_hidden;
+                share.messages.info(if self.show_hidden {
+                    "showing hidden files"
+                } else {
+                    "hiding hidden files"
+                });
+                self.request_rescan_files_then_select_current_again();
+            }
+            // M -> toggle threaded mode based on searchbar
+            Action::Mode => {
+                self.updates.request_reset_search();
+                self.updates.request_redraw_filebar();
+                let text = self.search_text.as_str();
+                let mode = if text == "b" {
+                    Some(ScanFilesMode::Blocking)
+                } else if text == "t" {
+                    Some(ScanFilesMode::Threaded)
+                } else if let Some(timeout) = text.strip_prefix('b') {
+                    timeout.parse().ok().map(ScanFilesMode::Timeout)
+                } else if let Some(timeout) = text.strip_prefix('t') {
+                    timeout.parse().ok().map(ScanFilesMode::TimeoutThenThreaded)
+                } else {
+                    None
+                };
+                match mode {
+                    Some(mode) => {
+                        share.messages.info(match &mode {
+                            ScanFilesMode::Blocking => "listing files: blocking".to_owned(),
+                            ScanFilesMode::Threaded => "listing files: threaded".to_owned(),
+                            ScanFilesMode::Timeout(t) => {
+                                format!("listing files: blocking, stopping after {t}s")
                             }
-                            self.term_setup(share)?;
-                        }
-                        // 0-9 -> set scan_files_max_depth
-                        (Focus::Files, KeyCode::Char('0')) => {
-                            self.scan_files_max_depth = usize::MAX;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('1')) => {
-                            self.scan_files_max_depth = 0;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('2')) => {
-                            self.scan_files_max_depth = 1;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('3')) => {
-                            self.scan_files_max_depth = 2;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('4')) => {
-                            self.scan_files_max_depth = 3;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('5')) => {
-                            self.scan_files_max_depth = 4;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('6')) => {
-                            self.scan_files_max_depth = 5;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('7')) => {
-                            self.scan_files_max_depth = 6;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('8')) => {
-                            self.scan_files_max_depth = 7;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        (Focus::Files, KeyCode::Char('9')) => {
-                            self.scan_files_max_depth = 8;
-                            self.request_rescan_files_then_select_current_again();
-                        }
-                        // - - - SearchBar - - -
-                        // Esc -> Nevermind
-                        (Focus::SearchBar, KeyCode::Esc) => {
-                            self.focus = Focus::Files;
-                            self.search_text.clear();
-                            self.search_regex = None;
-                            self.updates.request_redraw_searchbar();
-                            self.updates.request_move_cursor();
-                            if share.live_search {
-                                self.updates.request_filter_files();
+                            ScanFilesMode::TimeoutThenThreaded(t) => {
+                                format!("listing files: blocking, threaded after {t}s")
                             }
+                        });
+                        self.scan_files_mode = mode;
+                    }
+                    None => share.messages.error(format!(
+                        "'{text}' is not a file list mode: use b, t, b<seconds> or t<seconds>"
+                    )),
+                }
+            }
+            // N -> New Directory based on search bar
+            Action::NewDir => {
+                if self.search_text.is_empty() {
+                    share.messages.warning(
+                        "type the name of the new directory into the find/filter bar first",
+                    );
+                    return Ok(None);
+                }
+                let dir = self.current_dir.join(&self.search_text);
+                match fs::create_dir_all(&dir) {
+                    Ok(()) => {
+                        share.messages.info(format!("created {}", dir.display()));
+                        self.updates.request_reset_search();
+                        self.current_dir = dir;
+                        self.updates.request_redraw_infobar();
+                    }
+                    Err(e) => share
+                        .messages
+                        .error(format!("couldn't create {}: {e}", dir.display())),
+                }
+                self.updates.request_rescan_files();
+            }
+            // Y -> Yank (copy) the selected entries to the clipboard, U -> cUt them
+            Action::Yank | Action::Cut => {
+                let entries: Vec<_> = self
+                    .selected_entries()
+                    .into_iter()
+                    .map(|(rel_path, recursive)| ClipboardEntry {
+                        parent: self.current_dir.clone(),
+                        rel_path,
+                        recursive,
+                    })
+                    .collect();
+                if entries.is_empty() {
+                    share.messages.warning("nothing is selected");
+                } else {
+                    let verb = if action == Action::Cut {
+                        "cut"
+                    } else {
+                        "yanked"
+                    };
+                    share
+                        .messages
+                        .info(format!("{verb} {}", messages::entries(entries.len())));
+                }
+                share.clipboard = (!entries.is_empty()).then_some(Clipboard {
+                    cut: action == Action::Cut,
+                    entries,
+                });
+                self.updates.request_redraw_infobar();
+            }
+            // C -> paste (Copy or move) the clipboard to this directory
+            Action::Paste => {
+                if let Some(clipboard) = &share.clipboard {
+                    let sources = clipboard.sources();
+                    if clipboard.cut {
+                        // the entries aren't where they were anymore
+                        share.clipboard = None;
+                        tasks::task_move(sources, self.current_dir.clone(), share);
+                    } else {
+                        tasks::task_copy(sources, self.current_dir.clone(), share);
+                    }
+                    self.updates.request_redraw_infobar();
+                } else {
+                    share
+                        .messages
+                        .warning("the clipboard is empty: yank (Y) or cut (U) entries first");
+                }
+            }
+            // G -> show the instances side by side, above each other, or only this one (Grid)
+            Action::Layout => {
+                share.layout = share.layout.next();
+                share
+                    .messages
+                    .info(format!("layout: {}", share.layout.name()));
+                return Ok(Some(AppCmd::Redraw));
+            }
+            // Z -> toggle the preview pane
+            Action::Preview => {
+                self.show_preview = !self.show_preview;
+                self.preview = None;
+                self.updates.request_redraw_filelist();
+            }
+            // < and > -> make the preview pane wider or narrower
+            Action::PreviewWider | Action::PreviewNarrower if self.show_preview => {
+                share.preview_width = if action == Action::PreviewWider {
+                    (share.preview_width + 5).min(90)
+                } else {
+                    share.preview_width.saturating_sub(5).max(10)
+                };
+                share
+                    .messages
+                    .info(format!("preview pane width: {}%", share.preview_width));
+                self.updates.request_redraw_filelist();
+            }
+            Action::PreviewWider | Action::PreviewNarrower => {
+                share
+                    .messages
+                    .warning("the preview pane is hidden: show it with Z");
+            }
+            // I -> Inspect the clipboard
+            Action::Inspect if share.clipboard.is_some() => {
+                self.clipboard_view = Some(0);
+                self.updates.request_redraw_filelist();
+            }
+            Action::Inspect => share.messages.warning("the clipboard is empty"),
+            // R -> Remove
+            Action::Remove => {
+                let mut paths = self
+                    .dir_content
+                    .iter()
+                    .rev()
+                    .filter(|e| e.selected)
+                    .map(|e| e.path.clone())
+                    .collect::<Vec<_>>();
+                // directories have to be empty before they can be removed,
+                // so remove the deepest paths first
+                paths.sort_by_key(|p| std::cmp::Reverse(p.components().count()));
+                if paths.is_empty() {
+                    share.messages.warning("nothing is selected");
+                    return Ok(None);
+                }
+                self.updates.request_redraw_infobar();
+                tasks::task_del(paths, share);
+            }
+            // P -> Permissions
+            Action::Permissions => {
+                self.updates.request_reset_search();
+                match u32::from_str_radix(&self.search_text, 8) {
+                    Ok(mode) => {
+                        let paths: Vec<_> = self
+                            .dir_content
+                            .iter()
+                            .rev()
+                            .filter(|e| e.selected)
+                            .map(|e| e.path.clone())
+                            .collect();
+                        if paths.is_empty() {
+                            share.messages.warning("nothing is selected");
+                            return Ok(None);
                         }
-                        // Enter -> Apply
-                        (Focus::SearchBar, KeyCode::Enter) => {
-                            self.focus = Focus::Files;
-                            self.updates.request_move_cursor();
-                            if !share.live_search {
-                                self.updates.request_filter_files();
-                            }
-                            self.updates.request_reset_current_index();
+                        self.updates.request_redraw_infobar();
+                        tasks::task_chmod(paths, mode, share);
+                    }
+                    Err(_) => share.messages.error(format!(
+                        "'{}' is not a mode: type it as a base-8 number like 644",
+                        self.search_text
+                    )),
+                }
+            }
+            // O -> Owner (and group)
+            Action::Owner => {
+                self.updates.request_reset_search();
+                // TODO!
+                share
+                    .messages
+                    .warning("setting the owner isn't supported yet");
+            }
+            // Query files (Edit doesn't do this automatically, but running a shell does)
+            Action::Rescan => {
+                // in case a directory changed without changing its mtime
+                share.scan_cache.lock().unwrap().clear();
+                return Ok(Some(AppCmd::RescanFiles));
+            }
+            // B -> saved filters (Bookmarks)
+            Action::SavedFilters => {
+                self.saved_picker = Some(SavedPicker {
+                    name: String::new(),
+                    index: 0,
+                    with_selection: false,
+                });
+                self.updates.request_redraw_filelist();
+            }
+            // V -> View the matching lines (when filtering by content)
+            Action::ViewMatches => {
+                if self.content_search.is_none() {
+                    share
+                        .messages
+                        .warning("only possible when filtering by content");
+                } else if let Some(entry) = self.dir_content.get(self.current_index) {
+                    if self
+                        .content_search
+                        .as_ref()
+                        .is_some_and(|s| s.results.lock().unwrap().files.contains_key(&entry.path))
+                    {
+                        self.content_preview = Some(entry.path.clone());
+                        self.updates.request_redraw_filelist();
+                    }
+                }
+            }
+            // W -> Shell (write a command, also it's they key above S for shell, and it's near E for Edit)
+            Action::Shell => {
+                Self::term_reset(share)?;
+                if let Some(file) = self.dir_content.get(self.current_index) {
+                    eprintln!(
+                        "Exit shell to return to TUIFILE. Selected file: {}",
+                        file.path.to_string_lossy()
+                    );
+                } else {
+                    eprintln!("Exit shell to return to TUIFILE.");
+                }
+                match Command::new(&share.shell_command)
+                    .current_dir(&self.current_dir)
+                    .status()
+                {
+                    Ok(s) => {
+                        if !s.success() {
+                            std::thread::sleep(Duration::from_secs(2));
+                            share
+                                .messages
+                                .warning(format!("{} exited with {s}", share.shell_command));
                         }
-                        (Focus::SearchBar, KeyCode::Char(ch)) => {
-                            self.search_text.push(ch);
-                            self.search_regex = None;
-                            self.updates.request_redraw_searchbar();
-                            if share.live_search {
-                                self.updates.request_filter_files();
-                            }
+                    }
+                    Err(e) => share
+                        .messages
+                        .error(format!("couldn't run {}: {e}", share.shell_command)),
+                }
+                self.term_setup(share)?;
+                return Ok(Some(AppCmd::RescanFiles));
+            }
+            // E -> Edit
+            Action::Edit => {
+                Self::term_reset(share)?;
+                let entry_path = self
+                    .dir_content
+                    .get(self.current_index)
+                    .map(|v| &v.path)
+                    .unwrap_or(&self.current_dir);
+                match Command::new(&share.editor_command)
+                    .arg(entry_path)
+                    .current_dir(&self.current_dir)
+                    .status()
+                {
+                    Ok(s) => {
+                        if !s.success() {
+                            std::thread::sleep(Duration::from_secs(2));
+                            share
+                                .messages
+                                .warning(format!("{} exited with {s}", share.editor_command));
                         }
-                        (Focus::SearchBar, KeyCode::Backspace) => {
-                            self.search_text.pop();
-                            self.search_regex = None;
-                            self.updates.request_redraw_searchbar();
-                            if share.live_search {
-                                self.updates.request_filter_files();
-                            }
+                    }
+                    Err(e) => share
+                        .messages
+                        .error(format!("couldn't run {}: {e}", share.editor_command)),
+                }
+                self.term_setup(share)?;
+                return Ok(Some(AppCmd::Redraw));
+            }
+            // 0-9 -> set scan_files_max_depth (and leave tree mode)
+            Action::Depth => {
+                if let KeyCode::Char(ch) = e.code {
+                    self.scan_files_max_depth = match ch {
+                        '0' => usize::MAX,
+                        ch => ch as usize - '1' as usize,
+                    };
+                    share.messages.info(match ch {
+                        '0' => "no depth limit".to_owned(),
+                        ch => format!("depth limit: {ch}"),
+                    });
+                    self.tree_mode = false;
+                    self.request_rescan_files_then_select_current_again();
+                }
+            }
+            // - - - PathBar - - -
+            Action::JumpToAncestor => {
+                self.path_jump = false;
+                self.updates.request_redraw_infobar();
+                if let Some(number) = match e.code {
+                    KeyCode::Char(ch) => ch.to_digit(10),
+                    _ => None,
+                } {
+                    match path_bar::ancestor(
+                        &self.current_dir,
+                        share.home.as_deref(),
+                        number as usize,
+                    ) {
+                        Some(dir) => self.go_to_ancestor(dir),
+                        None => share
+                            .messages
+                            .warning(format!("there is no directory {number} in the path bar")),
+                    }
+                }
+            }
+            Action::PathJumpCancel => {
+                self.path_jump = false;
+                self.updates.request_redraw_infobar();
+            }
+            // - - - SelectBar - - -
+            Action::SelectCancel => {
+                self.focus = Focus::Files;
+                self.history_index = None;
+                self.select_text.clear();
+                // the error below the search bar is the search text's again
+                self.update_search_filter();
+                self.updates.request_move_cursor();
+            }
+            Action::SelectApply => {
+                let deselect = matches!(self.focus, Focus::SelectBar { deselect: true });
+                match self.select_matching(deselect, share) {
+                    Ok(()) => {
+                        self.focus = Focus::Files;
+                        self.history_index = None;
+                        share.history.add(&self.select_text);
+                        self.select_text.clear();
+                        self.update_search_filter();
+                        self.updates.request_redraw_filelist();
+                        self.updates.request_move_cursor();
+                    }
+                    Err(e) => self.set_search_error(Some(e)),
+                }
+            }
+            Action::SelectType => {
+                if let KeyCode::Char(ch) = e.code {
+                    self.history_index = None;
+                    self.select_text.push(ch);
+                    self.set_search_error(self.select_filter().err());
+                }
+            }
+            Action::SelectBackspace => {
+                self.history_index = None;
+                self.select_text.pop();
+                self.set_search_error(self.select_filter().err());
+            }
+            // - - - SearchBar and SelectBar - - -
+            // Ctrl+R -> search the history (again, for an older entry)
+            Action::HistorySearch => {
+                match &mut self.history_search {
+                    Some(search) => {
+                        if let Some(i) = share.history.search(&search.query, search.found) {
+                            search.found = Some(i);
                         }
-                        _ => {}
-                    },
-                    Event::Paste(_e) => {}
-                    Event::Resize(w, h) => {
-                        share.size.0 = w;
-                        share.size.1 = h;
-                        self.updates.request_redraw();
+                    }
+                    None => {
+                        self.history_search = Some(HistorySearch {
+                            query: String::new(),
+                            found: share.history.search("", None),
+                        })
                     }
                 }
+                self.updates.request_redraw_searchbar();
             }
+            Action::HistoryType => {
+                if let (Some(search), KeyCode::Char(ch)) = (&mut self.history_search, e.code) {
+                    search.query.push(ch);
+                    search.found = share.history.search(&search.query, None);
+                }
+                self.updates.request_redraw_searchbar();
+            }
+            Action::HistoryBackspace => {
+                if let Some(search) = &mut self.history_search {
+                    search.query.pop();
+                    search.found = share.history.search(&search.query, None);
+                }
+                self.updates.request_redraw_searchbar();
+            }
+            // Enter -> use the entry which was found, Esc -> go back to the text you typed
+            Action::HistoryUse | Action::HistoryCancel => {
+                if let Some(search) = self.history_search.take() {
+                    if let (Action::HistoryUse, Some(text)) =
+                        (action, search.found.and_then(|i| share.history.get(i)))
+                    {
+                        self.history_index = None;
+                        self.set_prompt_text(text.to_owned(), share);
+                    }
+                }
+                self.updates.request_redraw_searchbar();
+            }
+            // Up/Down -> go through the history
+            Action::HistoryPrev => {
+                let index = match self.history_index {
+                    Some(i) => i.checked_sub(1),
+                    None => share.history.len().checked_sub(1),
+                };
+                if let Some(i) = index {
+                    if self.history_index.is_none() {
+                        self.history_draft = self.prompt_text().to_owned();
+                    }
+                    self.history_index = Some(i);
+                    let text = share.history.get(i).unwrap_or("").to_owned();
+                    self.set_prompt_text(text, share);
+                }
+            }
+            Action::HistoryNext => {
+                if let Some(i) = self.history_index {
+                    let text = if i + 1 < share.history.len() {
+                        self.history_index = Some(i + 1);
+                        share.history.get(i + 1).unwrap_or("").to_owned()
+                    } else {
+                        self.history_index = None;
+                        std::mem::take(&mut self.history_draft)
+                    };
+                    self.set_prompt_text(text, share);
+                }
+            }
+            // - - - SearchBar - - -
+            // Esc -> Nevermind
+            Action::SearchCancel => {
+                self.focus = Focus::Files;
+                self.selected_filter = None;
+                self.history_index = None;
+                // discarded, but can be recalled from the history
+                share.history.add(&self.search_text);
+                self.search_text.clear();
+                self.update_search_filter();
+                self.updates.request_move_cursor();
+                if share.live_search {
+                    self.updates.request_filter_files();
+                }
+            }
+            // Enter -> Apply
+            Action::SearchApply => {
+                self.focus = Focus::Files;
+                self.selected_filter = None;
+                self.history_index = None;
+                share.history.add(&self.search_text);
+                self.updates.request_redraw_searchbar();
+                self.updates.request_move_cursor();
+                if !share.live_search {
+                    self.updates.request_filter_files();
+                }
+                self.updates.request_reset_current_index();
+            }
+            // Ctrl+P -> match the filter against Paths (relative to the current directory) instead of names
+            Action::FilterPaths => {
+                self.filter_paths = !self.filter_paths;
+                self.updates.request_redraw_filebar();
+                self.updates.request_filter_files();
+            }
+            // Ctrl+T -> keep the ancestors of matching entries visible, so the Tree still makes sense
+            Action::FilterAncestors => {
+                self.filter_ancestors = !self.filter_ancestors;
+                self.updates.request_redraw_filebar();
+                self.updates.request_filter_files();
+            }
+            // Ctrl+A -> Add the filter to the stack and clear the search text
+            Action::PushFilter => {
+                match &self.search_filter {
+                    // the text is invalid, the last valid filter isn't what is shown
+                    _ if self.search_error.is_some() => {}
+                    Some(filter) if filter.content_regex().is_some() => {
+                        self.set_search_error(Some(
+                            "content searches can't be stacked".to_owned().into(),
+                        ));
+                    }
+                    Some(filter) => {
+                        self.filter_stack.push(filter.clone());
+                        share.history.add(&self.search_text);
+                        self.history_index = None;
+                        self.search_text.clear();
+                        self.update_search_filter();
+                        self.updates.request_filter_files();
+                    }
+                    None => {}
+                }
+            }
+            // Left/Right -> select a filter on the stack
+            Action::PrevFilter => {
+                self.selected_filter = match self.selected_filter {
+                    Some(i) => Some(i.saturating_sub(1)),
+                    None => self.filter_stack.len().checked_sub(1),
+                };
+                self.updates.request_redraw_searchbar();
+            }
+            Action::NextFilter => {
+                self.selected_filter = self
+                    .selected_filter
+                    .map(|i| i + 1)
+                    .filter(|i| *i < self.filter_stack.len());
+                self.updates.request_redraw_searchbar();
+            }
+            // ! -> invert the selected filter
+            Action::InvertFilter => {
+                if let Some(i) = self.selected_filter {
+                    self.filter_stack[i].negated = !self.filter_stack[i].negated;
+                }
+                self.updates.request_redraw_searchbar();
+                self.updates.request_filter_files();
+            }
+            // Backspace/Delete -> remove the selected filter
+            Action::RemoveFilter => {
+                if let Some(i) = self.selected_filter.take() {
+                    self.filter_stack.remove(i);
+                }
+                self.updates.request_redraw_searchbar();
+                self.updates.request_filter_files();
+            }
+            // Tab -> Change filter syntax
+            Action::NextSyntax => {
+                self.filter_syntax = self.filter_syntax.next();
+                self.update_search_filter();
+                self.updates.request_redraw_filebar();
+                if share.live_search {
+                    self.updates.request_filter_files();
+                }
+            }
+            Action::SearchType => {
+                if let KeyCode::Char(ch) = e.code {
+                    self.selected_filter = None;
+                    self.history_index = None;
+                    self.search_text.push(ch);
+                    self.update_search_filter();
+                    if share.live_search {
+                        self.updates.request_filter_files();
+                    }
+                }
+            }
+            Action::SearchBackspace => {
+                self.history_index = None;
+                self.search_text.pop();
+                self.update_search_filter();
+                if share.live_search {
+                    self.updates.request_filter_files();
+                }
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+}
+
+/// draws `lines` where the file list would be, filling `height` lines.
+/// `styled` gets the index of the line and its text, which is cut off or padded to the terminal's width.
+fn draw_lines(
+    share: &mut Share,
+    height: usize,
+    lines: &[String],
+    styled: impl Fn(usize, String) -> StyledContent<String>,
+) -> io::Result<()> {
+    let width = share.size.0 as usize;
+    for i in 0..height {
+        let line = lines.get(i).map_or("", |line| line.as_str());
+        let line = width::escape_control(line);
+        let (line, line_width) = width::truncate(&line, width);
+        let mut text = line.to_owned();
+        for _ in line_width..width {
+            text.push(' ');
+        }
+        queue!(
+            share.stdout,
+            cursor::MoveTo(share.origin.0, share.origin.1 + 2 + i as u16),
+            style::PrintStyledContent(styled(i, text))
+        )?;
+    }
+    Ok(())
+}
+
+/// splits `text` into parts which are (true) or aren't (false) highlighted.
+/// `highlight` are the indices of the highlighted chars, in ascending order.
+fn split_highlighted(text: String, highlight: impl Iterator<Item = usize>) -> Vec<(String, bool)> {
+    let mut highlight = highlight.peekable();
+    if highlight.peek().is_none() {
+        return vec![(text, false)];
+    }
+    let mut parts: Vec<(String, bool)> = vec![];
+    for (i, ch) in text.chars().enumerate() {
+        let highlighted = highlight.next_if_eq(&i).is_some();
+        match parts.last_mut() {
+            Some((part, h)) if *h == highlighted => part.push(ch),
+            _ => parts.push((ch.to_string(), highlighted)),
         }
     }
+    parts
 }
@@ -0,0 +1,239 @@
+use std::{borrow::Cow, fs, io, path::PathBuf};
+
+/// A filter stack and search text, and optionally selected files, saved under a name.
+#[derive(Clone)]
+pub(crate) struct SavedFilter {
+    pub name: String,
+    /// the filters on the stack, as shown in the search bar (`!r:\.bak$`)
+    pub filters: Vec<String>,
+    /// the search text, with the prefix for its syntax
+    pub search: String,
+    /// paths of the selected entries, relative to the directory they were listed in
+    pub selection: Vec<PathBuf>,
+}
+
+/// The saved filters, read from `saved` in the config directory (next to `config`).
+///
+/// Each filter starts with a `[name]` line, followed by `key = value` lines:
+/// `filter = <text>` (once per filter on the stack), `search = <text>` and `select = <path>` (once per selected file).
+/// Values which start or end with a space (or start with `"`) are written in `"`.
+pub(crate) struct SavedFilters {
+    pub list: Vec<SavedFilter>,
+    path: Option<PathBuf>,
+}
+
+impl SavedFilters {
+    pub fn default_path() -> Option<PathBuf> {
+        crate::config::Config::default_path().map(|path| path.with_file_name("saved"))
+    }
+    /// loads the saved filters. if the file doesn't exist, there are none.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, String> {
+        let mut list: Vec<SavedFilter> = vec![];
+        let file = match &path {
+            Some(v) => v,
+            None => return Ok(Self { list, path }),
+        };
+        let text = match fs::read_to_string(file) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self { list, path }),
+            Err(e) => return Err(format!("{}: {e}", file.display())),
+        };
+        for (line_nr, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("{}:{}: {msg}", file.display(), line_nr + 1);
+            if let Some(name) = line.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                list.push(SavedFilter {
+                    name: name.to_owned(),
+                    filters: vec![],
+                    search: String::new(),
+                    selection: vec![],
+                });
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => return Err(err(format!("expected 'key = value', found '{line}'"))),
+            };
+            let saved = match list.last_mut() {
+                Some(v) => v,
+                None => return Err(err("expected '[name]' before the first key".to_owned())),
+            };
+            let value = unquote(value);
+            match key {
+                "filter" => saved.filters.push(value.to_owned()),
+                "search" => saved.search = value.to_owned(),
+                "select" => saved.selection.push(PathBuf::from(value)),
+                _ => return Err(err(format!("unknown key '{key}'"))),
+            }
+        }
+        Ok(Self { list, path })
+    }
+    fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let mut text = String::new();
+        for saved in &self.list {
+            text.push_str(&format!("[{}]\n", saved.name));
+            for filter in &saved.filters {
+                text.push_str(&format!("filter = {}\n", quote(filter)));
+            }
+            if !saved.search.is_empty() {
+                text.push_str(&format!("search = {}\n", quote(&saved.search)));
+            }
+            for path in &saved.selection {
+                let path = path.to_string_lossy();
+                text.push_str(&format!("select = {}\n", quote(&path)));
+            }
+            text.push('\n');
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        }
+        fs::write(path, text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+    /// adds `saved`, replacing a saved filter with the same name, and writes the file.
+    /// names which start or end with a space, and anything with a line break, couldn't be read back.
+    pub fn set(&mut self, saved: SavedFilter) -> Result<(), String> {
+        if saved.name.trim() != saved.name {
+            return Err(format!(
+                "'{}': names can't start or end with a space",
+                saved.name
+            ));
+        }
+        let line_break = |text: &str| text.contains(['\n', '\r']);
+        if [&saved.name, &saved.search]
+            .into_iter()
+            .chain(&saved.filters)
+            .any(|text| line_break(text))
+            || saved
+                .selection
+                .iter()
+                .any(|path| line_break(&path.to_string_lossy()))
+        {
+            return Err(format!(
+                "'{}': saved filters can't contain line breaks",
+                saved.name.escape_debug()
+            ));
+        }
+        match self.list.iter_mut().find(|v| v.name == saved.name) {
+            Some(v) => *v = saved,
+            None => self.list.push(saved),
+        }
+        self.save()
+    }
+    pub fn remove(&mut self, index: usize) -> Result<(), String> {
+        if index < self.list.len() {
+            self.list.remove(index);
+        }
+        self.save()
+    }
+}
+
+/// `value` in `"` if `load` would change it otherwise
+fn quote(value: &str) -> Cow<'_, str> {
+    if value.trim() != value || value.starts_with('"') {
+        Cow::Owned(format!("\"{value}\""))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+/// removes the `"` added by `quote`
+fn unquote(value: &str) -> &str {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(v) => v,
+        None => value,
+    }
+}
+
+/// The state of the list of saved filters, while it is shown
+pub(crate) struct SavedPicker {
+    /// the name the current filter will be saved as
+    pub name: String,
+    /// the highlighted saved filter
+    pub index: usize,
+    /// if true, the selected files are saved too
+    pub with_selection: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{SavedFilter, SavedFilters};
+
+    fn saved(name: &str, search: &str) -> SavedFilter {
+        SavedFilter {
+            name: name.to_owned(),
+            filters: vec![],
+            search: search.to_owned(),
+            selection: vec![],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("tuifile-saved-{}", std::process::id()));
+        let path = dir.join("saved");
+        let mut filters = SavedFilters::load(Some(path.clone())).unwrap();
+        let mut first = saved("first [1]", " foo ");
+        first.filters = vec![
+            "!r:\\.bak$".to_owned(),
+            "\"quoted\"".to_owned(),
+            "f:a ".to_owned(),
+        ];
+        first.selection = vec![PathBuf::from(" a/b"), PathBuf::from("c d")];
+        filters.set(first).unwrap();
+        filters.set(saved("second", "g:*.rs")).unwrap();
+        // replaces the first one
+        filters.set(saved("second", "s:x = y")).unwrap();
+        let loaded = SavedFilters::load(Some(path)).unwrap();
+        assert_eq!(loaded.list.len(), 2);
+        let (first, second) = (&loaded.list[0], &loaded.list[1]);
+        assert_eq!(first.name, "first [1]");
+        assert_eq!(first.search, " foo ");
+        assert_eq!(first.filters, ["!r:\\.bak$", "\"quoted\"", "f:a "]);
+        assert_eq!(
+            first.selection,
+            [PathBuf::from(" a/b"), PathBuf::from("c d")]
+        );
+        assert_eq!(second.name, "second");
+        assert_eq!(second.search, "s:x = y");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn invalid_names() {
+        let mut filters = SavedFilters::load(None).unwrap();
+        assert!(filters.set(saved(" name", "a")).is_err());
+        assert!(filters.set(saved("name ", "a")).is_err());
+        assert!(filters.set(saved("two\nlines", "a")).is_err());
+        assert!(filters.set(saved("name", "two\nlines")).is_err());
+        assert!(filters.list.is_empty());
+        assert!(filters.set(saved("name", "a")).is_ok());
+    }
+
+    #[test]
+    fn errors() {
+        let dir = std::env::temp_dir().join(format!("tuifile-saved-errors-{}", std::process::id()));
+        let path = dir.join("saved");
+        fs::create_dir_all(&dir).unwrap();
+        for (text, error) in [
+            ("search = a\n", ":1: expected '[name]' before the first key"),
+            (
+                "[a]\nsearch\n",
+                ":2: expected 'key = value', found 'search'",
+            ),
+            ("# comment\n[a]\n\nfind = a\n", ":4: unknown key 'find'"),
+        ] {
+            fs::write(&path, text).unwrap();
+            let result = SavedFilters::load(Some(path.clone())).map(|_| ());
+            assert_eq!(result, Err(format!("{}{error}", path.display())));
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,402 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::prelude::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::cache::{self, SharedScanCache};
+use crate::glob::Glob;
+use crate::width;
+use crate::{DirContent, DirContentType};
+
+const BYTE_UNITS: [&str; 6] = ["", "K", "M", "G", "T", "P"];
+
+/// Everything `get_files` needs to know about how to list a directory.
+/// Cloned into the background thread in threaded mode.
+#[derive(Clone)]
+pub(crate) struct ScanOptions {
+    /// ignored in tree mode
+    pub max_depth: usize,
+    /// Some in tree mode: only these directories are scanned (in addition to the listed directory).
+    pub expanded: Option<HashSet<PathBuf>>,
+    /// if false, entries whose name starts with a `.` are skipped.
+    pub show_hidden: bool,
+    /// entries matching any of these are skipped, and their contents aren't scanned.
+    /// globs containing a `/` are matched against the path relative to the scanned directory,
+    /// all others are matched against the entry's name.
+    pub exclude: Vec<Glob>,
+    /// if true, directories on a different device than the scanned directory aren't scanned.
+    pub one_file_system: bool,
+    /// at most this many entries are listed per directory, the rest is replaced by a
+    /// `DirContentType::More` entry which can be expanded later. 0 means no limit.
+    pub entry_limit: usize,
+    pub cache: SharedScanCache,
+}
+
+/// why the contents of a directory weren't listed even though the depth limit would have allowed it.
+#[derive(Clone, Copy)]
+pub(crate) enum SkipReason {
+    /// the directory is on a different device than the listed directory (`one_file_system`)
+    OtherFileSystem,
+    /// the directory is one of its own parents (through a symlink or bind mount)
+    Loop,
+    /// the directory was already listed somewhere else (through a symlink or bind mount)
+    AlreadyListed,
+}
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OtherFileSystem => write!(f, "other file system"),
+            Self::Loop => write!(f, "loop"),
+            Self::AlreadyListed => write!(f, "already listed"),
+        }
+    }
+}
+
+/// (device, inode), identifies a directory even if it can be reached through multiple paths.
+type DirId = (u64, u64);
+struct ScanState<'a> {
+    options: &'a ScanOptions,
+    root: &'a Path,
+    root_dev: u64,
+    time_limit: Option<(Instant, f32)>,
+    /// all directories which have been (or are being) scanned
+    visited: HashSet<DirId>,
+    /// the directories which are currently being scanned, from the root to the deepest one
+    ancestors: Vec<DirId>,
+}
+impl<'a> ScanState<'a> {
+    fn new(root: &'a Path, options: &'a ScanOptions, timeout: Option<f32>) -> Result<Self, String> {
+        let root_id = fs::metadata(root)
+            .map(|v| (v.dev(), v.ino()))
+            .map_err(|e| format!("{e}"))?;
+        Ok(Self {
+            options,
+            root,
+            root_dev: root_id.0,
+            time_limit: timeout.map(|v| (Instant::now(), v)),
+            visited: [root_id].into_iter().collect(),
+            ancestors: vec![root_id],
+        })
+    }
+    /// Ok(None) if `path` isn't a directory, Ok(Some) if it should be scanned, Err if it shouldn't.
+    fn check_descend(&self, path: &Path) -> Result<Option<DirId>, SkipReason> {
+        // follows symlinks, just like `fs::read_dir`
+        let metadata = match fs::metadata(path) {
+            Ok(v) if v.is_dir() => v,
+            _ => return Ok(None),
+        };
+        let id = (metadata.dev(), metadata.ino());
+        if self.options.one_file_system && id.0 != self.root_dev {
+            Err(SkipReason::OtherFileSystem)
+        } else if self.ancestors.contains(&id) {
+            Err(SkipReason::Loop)
+        } else if self.visited.contains(&id) {
+            Err(SkipReason::AlreadyListed)
+        } else {
+            Ok(Some(id))
+        }
+    }
+}
+
+impl ScanOptions {
+    fn skip(&self, name: &str, rel_path: &Path) -> bool {
+        if !self.show_hidden && name.starts_with('.') {
+            return true;
+        }
+        self.exclude.iter().any(|glob| {
+            if glob.matches_paths() {
+                glob.is_match(&rel_path.to_string_lossy())
+            } else {
+                glob.is_match(name)
+            }
+        })
+    }
+}
+
+/// returns the directory's contents and true, or, if the time limit was reached, a partial list and false.
+/// entries are returned as `DirContentType::Pending`, see `DirContent::load`.
+pub(crate) fn get_files(
+    dir: PathBuf,
+    options: &ScanOptions,
+    timeout: Option<f32>,
+) -> Result<(Vec<DirContent>, bool), String> {
+    let mut o = vec![];
+    let mut state = ScanState::new(&dir, options, timeout)?;
+    let completed = get_files_rec(&mut o, &mut state, dir.clone(), 0, 0)?;
+    Ok((o, completed))
+}
+
+/// lists the entries a `DirContentType::More` entry stands for (up to the entry limit).
+/// `root` is the directory the `more` entry was originally listed from.
+pub(crate) fn get_more_files(
+    root: &Path,
+    more: &DirContent,
+    options: &ScanOptions,
+) -> Result<Vec<DirContent>, String> {
+    let loaded = match more.more {
+        DirContentType::More { loaded, .. } => loaded,
+        _ => return Ok(vec![]),
+    };
+    get_files_in(root, more.path.clone(), more.rel_depth, loaded, options)
+}
+
+/// lists the contents of `dir`, a directory inside `root`, as entries at `depth`.
+/// the first `skip` entries are left out.
+pub(crate) fn get_files_in(
+    root: &Path,
+    dir: PathBuf,
+    depth: usize,
+    skip: usize,
+    options: &ScanOptions,
+) -> Result<Vec<DirContent>, String> {
+    // `get_files_rec` ignores errors for anything but the root
+    let metadata = fs::metadata(&dir).map_err(|e| format!("{e}"))?;
+    fs::read_dir(&dir).map_err(|e| format!("{e}"))?;
+    let mut o = vec![];
+    let mut state = ScanState::new(root, options, None)?;
+    let id = (metadata.dev(), metadata.ino());
+    state.visited.insert(id);
+    state.ancestors.push(id);
+    get_files_rec(&mut o, &mut state, dir, depth, skip)?;
+    Ok(o)
+}
+
+/// lists `dir`, skipping the first `skip` entries.
+fn get_files_rec(
+    dir_content: &mut Vec<DirContent>,
+    state: &mut ScanState,
+    dir: PathBuf,
+    depth: usize,
+    skip: usize,
+) -> Result<bool, String> {
+    let options = state.options;
+    let root = state.root;
+    match cache::read_dir_cached(&options.cache, &dir) {
+        Err(e) => {
+            if depth == 0 {
+                return Err(format!("{e}"));
+            }
+        }
+        Ok(files) => {
+            let mut files = files.filter(|(name, _)| {
+                let p = dir.join(name);
+                !options.skip(&name.to_string_lossy(), p.strip_prefix(root).unwrap_or(&p))
+            });
+            for (name, file_type) in files.by_ref().skip(skip).take(if options.entry_limit > 0 {
+                options.entry_limit
+            } else {
+                usize::MAX
+            }) {
+                let p = dir.join(&name);
+                // control characters are shown (and matched) escaped
+                let mut name = width::escape_control(&name.to_string_lossy()).into_owned();
+                // the file type doesn't follow symlinks and usually doesn't need to stat the file
+                if file_type.is_some_and(|t| t.is_dir()) {
+                    name.push('/');
+                }
+                dir_content.push(DirContent {
+                    path: p.clone(),
+                    name_width: width::width(&name),
+                    name,
+                    rel_depth: depth,
+                    passes_filter: true,
+                    selected: false,
+                    info: String::new(),
+                    tree: String::new(),
+                    more: DirContentType::Pending,
+                    skipped: None,
+                    order: 0,
+                    highlight: vec![],
+                    context: false,
+                });
+                if let Some((since, max)) = state.time_limit {
+                    if since.elapsed().as_secs_f32() > max {
+                        return Ok(false);
+                    }
+                }
+                let descend = match &options.expanded {
+                    Some(expanded) => expanded.contains(&p),
+                    None => depth < options.max_depth,
+                };
+                if descend && file_type.is_some_and(|t| t.is_dir() || t.is_symlink()) {
+                    match state.check_descend(&p) {
+                        Ok(None) => {}
+                        Ok(Some(id)) => {
+                            state.visited.insert(id);
+                            state.ancestors.push(id);
+                            // should (almost?) never return an error
+                            let completed = get_files_rec(dir_content, state, p, depth + 1, 0)?;
+                            state.ancestors.pop();
+                            if !completed {
+                                return Ok(false);
+                            }
+                        }
+                        Err(reason) => {
+                            if let Some(e) = dir_content.last_mut() {
+                                e.skipped = Some(reason);
+                            }
+                        }
+                    }
+                }
+            }
+            // only counts the remaining entries, this doesn't need to stat them.
+            let remaining = files.count();
+            if remaining > 0 {
+                let name = format!("{remaining} more not loaded");
+                dir_content.push(DirContent {
+                    path: dir,
+                    name_width: width::width(&name),
+                    name,
+                    rel_depth: depth,
+                    passes_filter: true,
+                    selected: false,
+                    info: String::new(),
+                    tree: String::new(),
+                    more: DirContentType::More {
+                        loaded: skip + options.entry_limit,
+                        remaining,
+                    },
+                    skipped: None,
+                    order: 0,
+                    highlight: vec![],
+                    context: false,
+                });
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// like `1023`, `12K` or `3G`
+pub(crate) fn format_size(mut bytes: u64) -> String {
+    let mut i = 0;
+    while bytes >= 1024 && i + 1 < BYTE_UNITS.len() {
+        i += 1;
+        bytes >>= 10;
+    }
+    format!("{bytes}{}", BYTE_UNITS[i])
+}
+
+impl DirContent {
+    /// true unless this is a `DirContentType::More` entry
+    pub fn is_entry(&self) -> bool {
+        !matches!(self.more, DirContentType::More { .. })
+    }
+    /// the text filters are matched against: the name, or if `paths` is true,
+    /// the path relative to `root`, with a '/' at the end for directories.
+    /// control characters are escaped like in the name.
+    pub fn filter_text(&self, root: &Path, paths: bool) -> Cow<'_, str> {
+        if paths {
+            let path = self.path.strip_prefix(root).unwrap_or(&self.path);
+            let mut path = width::escape_control(&path.to_string_lossy()).into_owned();
+            if self.name.ends_with('/') {
+                path.push('/');
+            }
+            Cow::Owned(path)
+        } else {
+            Cow::Borrowed(&self.name)
+        }
+    }
+    /// None if the metadata hasn't been loaded or couldn't be read
+    pub fn metadata(&self) -> Option<&fs::Metadata> {
+        match &self.more {
+            DirContentType::Dir { metadata }
+            | DirContentType::File { metadata }
+            | DirContentType::Symlink { metadata, .. } => Some(metadata),
+            DirContentType::Pending | DirContentType::More { .. } | DirContentType::Err(_) => None,
+        }
+    }
+    /// reads the metadata of a `DirContentType::Pending` entry and generates its info.
+    /// does nothing if the entry was already loaded.
+    /// if the entry's info is wider than the info table's columns, `info_widths` is updated.
+    pub fn load(&mut self, info_what: &[u32], info_widths: &mut Vec<usize>) {
+        if !matches!(self.more, DirContentType::Pending) {
+            return;
+        }
+        let metadata = fs::symlink_metadata(&self.path);
+        if let Ok(metadata) = &metadata {
+            // in each line:
+            // first char:
+            // < left-aligned
+            // > right-aligned
+            // anything else -> centered
+            // sep. line: "< | "
+            for info_what in info_what {
+                match info_what {
+                    0 => {
+                        // the number and the unit are in separate columns
+                        let size = format_size(metadata.len());
+                        let digits = size.find(|ch: char| !ch.is_ascii_digit());
+                        let (number, unit) = size.split_at(digits.unwrap_or(size.len()));
+                        self.info.push_str(&format!("< | \n>{number}\n>{unit}\n"));
+                    }
+                    1 => {
+                        self.info.push_str(&format!(
+                            "< | \n>{:03o}\n",
+                            metadata.permissions().mode() & 0o777,
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.more = match metadata {
+            Err(e) => DirContentType::Err(e.to_string()),
+            Ok(metadata) => {
+                if metadata.is_symlink() {
+                    DirContentType::Symlink {
+                        metadata,
+                        target: fs::metadata(&self.path).ok(),
+                    }
+                } else if metadata.is_file() {
+                    DirContentType::File { metadata }
+                } else if metadata.is_dir() {
+                    DirContentType::Dir { metadata }
+                } else {
+                    DirContentType::Err("not a file, dir or symlink".to_string())
+                }
+            }
+        };
+        // table-style: the columns grow as more entries are loaded
+        for (i, line) in self.info.lines().enumerate() {
+            if i >= info_widths.len() {
+                info_widths.push(0);
+            }
+            if line.len() > info_widths[i] {
+                info_widths[i] = line.len();
+            }
+        }
+    }
+    /// the entry's info, aligned to the info table's columns
+    pub fn info_aligned(&self, info_widths: &[usize]) -> String {
+        let mut info = String::new();
+        for (line, width) in self.info.lines().zip(info_widths) {
+            let rem = width.saturating_sub(line.len());
+            if let Some(line) = line.strip_prefix('<') {
+                info.push_str(line);
+                for _ in 0..rem {
+                    info.push(' ');
+                }
+            } else if let Some(line) = line.strip_prefix('>') {
+                for _ in 0..rem {
+                    info.push(' ');
+                }
+                info.push_str(line);
+            } else {
+                let r = rem / 2;
+                for _ in 0..r {
+                    info.push(' ');
+                }
+                info.push_str(&line[1..]);
+                for _ in 0..(rem - r) {
+                    info.push(' ');
+                }
+            }
+        }
+        info
+    }
+}
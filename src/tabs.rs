@@ -0,0 +1,130 @@
+use std::{io, ops::Range};
+
+use crossterm::{
+    cursor, queue,
+    style::{self, Attribute, ContentStyle, StyledContent},
+    terminal,
+};
+
+use crate::{width, Share, TuiFile};
+
+/// names longer than this are cut off in the tab bar
+const MAX_NAME_WIDTH: usize = 20;
+
+/// what the tab bar shows about an instance
+#[derive(Clone, PartialEq)]
+pub(crate) struct Tab {
+    /// the name of the instance's directory
+    name: String,
+    active: bool,
+    /// true if any entries are selected
+    selected: bool,
+    /// true while the files are listed in the background
+    scanning: bool,
+}
+
+impl Tab {
+    pub fn new(instance: &TuiFile, active: bool) -> Self {
+        let name = match instance.current_dir.file_name() {
+            Some(name) => width::escape_control(&name.to_string_lossy()).into_owned(),
+            None => width::escape_control(&instance.current_dir.to_string_lossy()).into_owned(),
+        };
+        Self {
+            name,
+            active,
+            selected: instance.dir_content.iter().any(|e| e.selected),
+            scanning: instance.dir_content_builder_task.is_some(),
+        }
+    }
+}
+
+/// The row at the top of the terminal which lists all instances.
+/// The active instance's tab is highlighted, `*` marks instances with selected entries
+/// and `…` instances which are still listing their files.
+#[derive(Default)]
+pub(crate) struct TabBar {
+    /// the tabs as they were last drawn
+    drawn: Vec<Tab>,
+    /// the columns each drawn tab takes up, and the index of its instance
+    columns: Vec<(Range<u16>, usize)>,
+}
+
+impl TabBar {
+    /// makes the next `draw` draw the tab bar even if no tab changed, like after the terminal was cleared
+    pub fn invalidate(&mut self) {
+        self.drawn.clear();
+    }
+    /// the index of the instance whose tab is drawn at column `x`
+    pub fn tab_at(&self, x: u16) -> Option<usize> {
+        self.columns
+            .iter()
+            .find(|(columns, _)| columns.contains(&x))
+            .map(|(_, index)| *index)
+    }
+}
+
+/// draws the tab bar into the top row of the terminal, unless the tabs didn't change since it was last drawn.
+/// if the tabs don't fit, the first ones are left out so the active tab is visible.
+/// returns true if the tab bar was drawn.
+pub(crate) fn draw(share: &mut Share, tabs: Vec<Tab>) -> io::Result<bool> {
+    let tab_bar = match &mut share.tab_bar {
+        Some(v) => v,
+        None => return Ok(false),
+    };
+    if tab_bar.drawn == tabs {
+        return Ok(false);
+    }
+    let labels: Vec<String> = tabs
+        .iter()
+        .enumerate()
+        .map(|(i, tab)| {
+            let name = match width::truncate(&tab.name, MAX_NAME_WIDTH) {
+                (name, _) if name.len() == tab.name.len() => name.to_owned(),
+                _ => format!("{}...", width::truncate(&tab.name, MAX_NAME_WIDTH - 3).0),
+            };
+            let selected = if tab.selected { " *" } else { "" };
+            let scanning = if tab.scanning { " …" } else { "" };
+            format!(" {i} {name}{selected}{scanning} ")
+        })
+        .collect();
+    let total_width = share.term_size.0 as usize;
+    let active = tabs.iter().position(|tab| tab.active).unwrap_or(0);
+    let mut first = 0;
+    while first < active
+        && labels[first..=active]
+            .iter()
+            .map(|label| width::width(label))
+            .sum::<usize>()
+            > total_width
+    {
+        first += 1;
+    }
+    tab_bar.columns.clear();
+    queue!(share.stdout, cursor::MoveTo(0, 0))?;
+    let mut x = 0;
+    for (i, label) in labels.iter().enumerate().skip(first) {
+        if x >= total_width {
+            break;
+        }
+        let (label, label_width) = width::truncate(label, total_width - x);
+        let mut style = ContentStyle::new();
+        if tabs[i].active {
+            style.attributes.set(Attribute::Reverse);
+            style.attributes.set(Attribute::Bold);
+        }
+        queue!(
+            share.stdout,
+            style::PrintStyledContent(StyledContent::new(style, label))
+        )?;
+        tab_bar
+            .columns
+            .push((x as u16..(x + label_width) as u16, i));
+        x += label_width;
+    }
+    queue!(
+        share.stdout,
+        terminal::Clear(terminal::ClearType::UntilNewLine)
+    )?;
+    tab_bar.drawn = tabs;
+    Ok(true)
+}
@@ -1,11 +1,82 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs, io,
     os::unix::prelude::PermissionsExt,
     path::{Path, PathBuf},
 };
 
-use crate::{BackgroundTask, Share};
+use crate::{messages::entries, BackgroundTask, DirContent, Share, TaskResult};
+
+/// the selected entries of `dir_content`, relative to `dir`, and whether each of them is copied with everything inside it.
+/// that's the case for directories which aren't `listed` (collapsed or below the depth limit), whose contents were skipped,
+/// or which contain entries that aren't listed (hidden, excluded or cut off by the entry limit).
+/// the entries inside those are left out, they are copied with the directory.
+pub(crate) fn selected_entries(
+    dir_content: &[DirContent],
+    dir: &Path,
+    listed: impl Fn(&DirContent) -> bool,
+) -> Vec<(PathBuf, bool)> {
+    let mut counts: HashMap<&Path, usize> = HashMap::new();
+    for e in dir_content.iter().filter(|e| e.is_entry()) {
+        if let Some(parent) = e.path.parent() {
+            *counts.entry(parent).or_default() += 1;
+        }
+    }
+    let recursive = |e: &DirContent| {
+        let count = counts.get(e.path.as_path()).copied().unwrap_or(0);
+        e.name.ends_with('/')
+            && (!listed(e)
+                || e.skipped.is_some()
+                || fs::read_dir(&e.path).is_ok_and(|entries| entries.count() > count))
+    };
+    let selected = dir_content
+        .iter()
+        .filter(|e| e.selected && e.is_entry())
+        .map(|e| (e, recursive(e)))
+        .collect::<Vec<_>>();
+    let recursive_dirs = selected
+        .iter()
+        .filter(|(_, recursive)| *recursive)
+        .map(|(e, _)| e.path.as_path())
+        .collect::<HashSet<_>>();
+    selected
+        .into_iter()
+        .filter(|(e, _)| {
+            !e.path
+                .ancestors()
+                .skip(1)
+                .any(|p| recursive_dirs.contains(p))
+        })
+        .filter_map(|(e, recursive)| Some((e.path.strip_prefix(dir).ok()?.to_owned(), recursive)))
+        .collect()
+}
+
+/// the entries a task couldn't handle, and why the first one failed
+#[derive(Default)]
+struct Failures {
+    count: usize,
+    first: Option<String>,
+}
+impl Failures {
+    fn check<T>(&mut self, path: &Path, result: io::Result<T>) {
+        if let Err(e) = result {
+            self.count += 1;
+            if self.first.is_none() {
+                self.first = Some(format!("{}: {e}", path.display()));
+            }
+        }
+    }
+    /// what a task which handled `total` entries reports: `done` if nothing failed
+    fn result(self, total: usize, verb: &str, done: String) -> TaskResult {
+        match self.first {
+            None => Ok(Some(done)),
+            Some(e) => Err(format!(
+                "{} of {total} entries couldn't be {verb}: {e}",
+                self.count
+            )),
+        }
+    }
+}
 
 pub(crate) fn task_copy(
     src: Vec<(PathBuf, Vec<(PathBuf, bool)>)>,
@@ -15,43 +86,168 @@ pub(crate) fn task_copy(
     share.tasks.push(BackgroundTask::new(
         "cp".to_string(),
         move |status| {
-            let mut total: usize = src.iter().map(|v| v.1.len()).sum();
-            for (parent, rel_paths) in src {
-                let mut created: HashSet<PathBuf> = HashSet::new();
-                for (rel_path, copy_recursive) in rel_paths {
-                    total = total.saturating_sub(1);
-                    {
-                        let s = format!("cp {total}");
-                        *status.lock().unwrap() = s;
-                    }
-                    let file_from = parent.join(&rel_path);
-                    let is_dir = file_from.is_dir();
-                    let file_to = if let Some(parent) = rel_path.parent() {
-                        let mut p = PathBuf::new();
-                        for c in parent.components() {
-                            p.push(c);
-                            if !created.contains(&p) {
-                                p.pop();
-                                break;
-                            }
-                        }
-                        target.join(&p).join(rel_path.file_name().unwrap())
-                    } else {
-                        target.join(&rel_path)
-                    };
-                    if is_dir {
-                        copy_dir(file_from, file_to, copy_recursive);
-                        created.insert(rel_path);
-                    } else {
-                        fs::copy(&file_from, &file_to);
-                    }
+            let count: usize = src.iter().map(|v| v.1.len()).sum();
+            let failures = copy_entries(src, &target, |total| {
+                *status.lock().unwrap() = format!("cp {total}");
+            });
+            let done = format!("copied {} to {}", entries(count), target.display());
+            failures.result(count, "copied", done)
+        },
+        true,
+    ));
+}
+/// copies the entries (grouped by the directory they are in, with the paths relative to it) into `target`.
+/// `progress` is called with the number of entries which are left.
+fn copy_entries(
+    src: Vec<(PathBuf, Vec<(PathBuf, bool)>)>,
+    target: &Path,
+    progress: impl Fn(usize),
+) -> Failures {
+    let mut total: usize = src.iter().map(|v| v.1.len()).sum();
+    let mut failures = Failures::default();
+    for (parent, rel_paths) in src {
+        let mut created: HashSet<PathBuf> = HashSet::new();
+        for (rel_path, copy_recursive) in rel_paths {
+            total = total.saturating_sub(1);
+            progress(total);
+            let file_from = parent.join(&rel_path);
+            let is_dir = file_from.is_dir();
+            let file_to = target_path(target, &rel_path, &created);
+            // pasting into the directory the entries came from would overwrite them
+            if file_to.starts_with(&file_from) {
+                failures.check(
+                    &file_from,
+                    Err::<(), _>(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "can't copy an entry into itself",
+                    )),
+                );
+                continue;
+            }
+            if is_dir {
+                // like when moving, the selected entries inside can go into a directory which already exists
+                if copy_recursive || !file_to.is_dir() {
+                    failures.check(&file_from, copy_dir(&file_from, file_to, copy_recursive));
                 }
+                created.insert(rel_path);
+            } else {
+                failures.check(&file_from, copy_file(&file_from, &file_to));
+            }
+        }
+    }
+    failures
+}
+/// copies the file at `from` to `to`, unless something already exists at `to`.
+fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+    fs::copy(from, to).map(|_| ())
+}
+/// where the entry at `rel_path` ends up: inside the directories which were `created` in `target`
+/// for its parents, or directly in `target` if its parent directory wasn't copied.
+fn target_path(target: &Path, rel_path: &Path, created: &HashSet<PathBuf>) -> PathBuf {
+    if let Some(parent) = rel_path.parent() {
+        let mut p = PathBuf::new();
+        for c in parent.components() {
+            p.push(c);
+            if !created.contains(&p) {
+                p.pop();
+                break;
             }
-            Ok(())
+        }
+        target.join(&p).join(rel_path.file_name().unwrap())
+    } else {
+        target.join(rel_path)
+    }
+}
+pub(crate) fn task_move(
+    src: Vec<(PathBuf, Vec<(PathBuf, bool)>)>,
+    target: PathBuf,
+    share: &mut Share,
+) {
+    share.tasks.push(BackgroundTask::new(
+        "mv".to_string(),
+        move |status| {
+            let count: usize = src.iter().map(|v| v.1.len()).sum();
+            let failures = move_entries(src, &target, |total| {
+                *status.lock().unwrap() = format!("mv {total}");
+            });
+            let done = format!("moved {} to {}", entries(count), target.display());
+            failures.result(count, "moved", done)
         },
         true,
     ));
 }
+/// moves the entries into `target`, like `copy_entries` copies them.
+fn move_entries(
+    src: Vec<(PathBuf, Vec<(PathBuf, bool)>)>,
+    target: &Path,
+    progress: impl Fn(usize),
+) -> Failures {
+    let mut total: usize = src.iter().map(|v| v.1.len()).sum();
+    let mut failures = Failures::default();
+    for (parent, rel_paths) in src {
+        let mut created: HashSet<PathBuf> = HashSet::new();
+        // directories whose contents were moved one by one, removed at the end if they are empty
+        let mut emptied = vec![];
+        for (rel_path, move_recursive) in rel_paths {
+            total = total.saturating_sub(1);
+            progress(total);
+            let file_from = parent.join(&rel_path);
+            let file_to = target_path(target, &rel_path, &created);
+            if file_from.is_dir() && !move_recursive {
+                // the entries inside are moved into a directory which already exists,
+                // each of them fails if it would replace something
+                if !file_to.is_dir() {
+                    failures.check(&file_from, fs::create_dir(&file_to));
+                }
+                created.insert(rel_path);
+                emptied.push(file_from);
+            } else {
+                failures.check(&file_from, move_path(&file_from, &file_to));
+            }
+        }
+        for dir in emptied.into_iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+    failures
+}
+/// renames `from` to `to`, or, if they are on different file systems, copies it and then removes it.
+/// files are only removed once they were copied, and nothing which already exists at `to` is replaced.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if to.starts_with(from) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't move an entry into itself",
+        ));
+    }
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() && !from.is_symlink() {
+        fs::create_dir(to)?;
+        // if anything inside couldn't be moved, the directory is kept
+        for e in fs::read_dir(from)? {
+            let e = e?;
+            move_path(&e.path(), &to.join(e.file_name()))?;
+        }
+        fs::remove_dir(from)
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    }
+}
 fn copy_dir(
     file_from: impl AsRef<Path>,
     file_to: impl AsRef<Path>,
@@ -60,15 +256,13 @@ fn copy_dir(
     fs::create_dir(&file_to)?;
     if recursive {
         if let Ok(e) = fs::read_dir(file_from) {
-            for e in e {
-                if let Ok(e) = e {
-                    let p = e.path();
-                    let t = file_to.as_ref().join(e.file_name());
-                    if p.is_dir() {
-                        copy_dir(p, t, recursive);
-                    } else {
-                        fs::copy(&p, &t);
-                    }
+            for e in e.flatten() {
+                let p = e.path();
+                let t = file_to.as_ref().join(e.file_name());
+                if p.is_dir() {
+                    let _ = copy_dir(p, t, recursive);
+                } else {
+                    let _ = fs::copy(&p, &t);
                 }
             }
         }
@@ -77,42 +271,153 @@ fn copy_dir(
 }
 
 pub(crate) fn task_del(paths: Vec<PathBuf>, share: &mut Share) {
-    let mut total: usize = paths.len();
+    let count = paths.len();
+    let mut total = count;
     share.tasks.push(BackgroundTask::new(
         format!("rm {total}"),
         move |status| {
+            let mut failures = Failures::default();
             for path in paths {
                 {
                     total -= 1;
                     let s = format!("rm {total}");
                     *status.lock().unwrap() = s;
                 }
-                if path.is_dir() {
-                    fs::remove_dir(path);
+                let result = if path.is_dir() {
+                    fs::remove_dir(&path)
                 } else {
-                    fs::remove_file(path);
-                }
+                    fs::remove_file(&path)
+                };
+                failures.check(&path, result);
             }
-            Ok(())
+            failures.result(count, "removed", format!("removed {}", entries(count)))
         },
         true,
     ));
 }
 pub(crate) fn task_chmod(paths: Vec<PathBuf>, mode: u32, share: &mut Share) {
-    let mut total = paths.len();
+    let count = paths.len();
+    let mut total = count;
     share.tasks.push(BackgroundTask::new(
         format!("chmod {total}"),
         move |status| {
+            let mut failures = Failures::default();
             for path in paths {
                 {
                     total -= 1;
                     let s = format!("chmod {total}");
                     *status.lock().unwrap() = s;
                 }
-                fs::set_permissions(path, fs::Permissions::from_mode(mode));
+                failures.check(
+                    &path,
+                    fs::set_permissions(&path, fs::Permissions::from_mode(mode)),
+                );
             }
-            Ok(())
+            let done = format!("changed the mode of {} to {mode:o}", entries(count));
+            failures.result(count, "changed", done)
         },
         true,
     ));
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use crate::{
+        clipboard::{Clipboard, ClipboardEntry},
+        scan::{self, ScanOptions},
+    };
+
+    /// an empty directory for a test, which is removed when it is dropped
+    struct TempDir(PathBuf);
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("tuifile-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn cut_collapsed_dir() {
+        let tmp = TempDir::new("cut");
+        let (src, target) = (tmp.0.join("src"), tmp.0.join("target"));
+        fs::create_dir_all(src.join("dir/sub")).unwrap();
+        fs::create_dir(&target).unwrap();
+        fs::write(src.join("dir/a"), "a").unwrap();
+        fs::write(src.join("dir/sub/b"), "b").unwrap();
+        fs::write(src.join("c"), "c").unwrap();
+        // tree mode, nothing expanded: only `dir/` and `c` are listed
+        let expanded = HashSet::new();
+        let options = ScanOptions {
+            max_depth: 0,
+            expanded: Some(expanded.clone()),
+            show_hidden: true,
+            exclude: vec![],
+            one_file_system: false,
+            entry_limit: 0,
+            cache: Default::default(),
+        };
+        let (mut entries, _) = scan::get_files(src.clone(), &options, None).unwrap();
+        assert_eq!(entries.len(), 2);
+        for e in &mut entries {
+            e.selected = e.name == "dir/";
+        }
+        let selected = super::selected_entries(&entries, &src, |e| expanded.contains(&e.path));
+        assert_eq!(selected, [(PathBuf::from("dir"), true)]);
+        let clipboard = Clipboard {
+            cut: true,
+            entries: selected
+                .into_iter()
+                .map(|(rel_path, recursive)| ClipboardEntry {
+                    parent: src.clone(),
+                    rel_path,
+                    recursive,
+                })
+                .collect(),
+        };
+        let failures = super::move_entries(clipboard.sources(), &target, |_| {});
+        assert_eq!(failures.count, 0);
+        assert_eq!(fs::read_to_string(target.join("dir/a")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(target.join("dir/sub/b")).unwrap(), "b");
+        assert!(!src.join("dir").exists());
+        assert!(src.join("c").exists());
+    }
+
+    #[test]
+    fn no_overwrite() {
+        let tmp = TempDir::new("no-overwrite");
+        let (src, target) = (tmp.0.join("src"), tmp.0.join("target"));
+        fs::create_dir_all(src.join("dir")).unwrap();
+        fs::create_dir_all(target.join("dir")).unwrap();
+        fs::write(src.join("a"), "new").unwrap();
+        fs::write(src.join("dir/b"), "new").unwrap();
+        fs::write(target.join("a"), "old").unwrap();
+        fs::write(target.join("dir/b"), "old").unwrap();
+        let sources = || {
+            vec![(
+                src.clone(),
+                vec![
+                    ("a".into(), false),
+                    ("dir".into(), false),
+                    ("dir/b".into(), false),
+                ],
+            )]
+        };
+        // the directory is used as it is, only the files fail
+        assert_eq!(super::copy_entries(sources(), &target, |_| {}).count, 2);
+        assert_eq!(super::move_entries(sources(), &target, |_| {}).count, 2);
+        assert_eq!(fs::read_to_string(target.join("a")).unwrap(), "old");
+        assert_eq!(fs::read_to_string(target.join("dir/b")).unwrap(), "old");
+        // what couldn't be moved is still there
+        assert_eq!(fs::read_to_string(src.join("a")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(src.join("dir/b")).unwrap(), "new");
+    }
+}
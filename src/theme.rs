@@ -0,0 +1,416 @@
+use std::{fs, fs::Metadata, os::unix::fs::PermissionsExt, path::Path};
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use crate::DirContentType;
+
+/// the colors of `LS_COLORS` codes 30 to 37 (and 40 to 47 for the background)
+const DARK_COLORS: [Color; 8] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+];
+/// the colors of `LS_COLORS` codes 90 to 97 (and 100 to 107 for the background)
+const BRIGHT_COLORS: [Color; 8] = [
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+/// The colors of the file list and the info bar.
+///
+/// These start out as tuifile's own colors, then `LS_COLORS` (unless `ls_colors = false` is set in the config)
+/// and the theme file (`theme = <name>` in the config) change them.
+/// Each line of the theme file is `key = value`, empty lines and lines starting with `#` are ignored.
+/// Keys are `file`, `dir`, `symlink`, `orphan`, `executable`, `error`, `path` and `home`, or `*<suffix>` (like `*.rs`) for files with that suffix.
+/// Values are codes like in `LS_COLORS` (`01;34`, `38;5;208`, `38;2;255;128;0`), or words like `bold blue on #202020`.
+pub(crate) struct Theme {
+    pub file: ContentStyle,
+    pub dir: ContentStyle,
+    /// None to use the style of the symlink's target (`ln=target` in `LS_COLORS`)
+    pub symlink: Option<ContentStyle>,
+    /// symlinks whose target doesn't exist. None to use `symlink`.
+    pub orphan: Option<ContentStyle>,
+    /// files which anyone may execute. None to use `file` (or the style of the file's suffix).
+    pub executable: Option<ContentStyle>,
+    pub error: ContentStyle,
+    /// the current directory in the info bar
+    pub path: ContentStyle,
+    /// the `~` the home directory is shown as in the info bar
+    pub home: ContentStyle,
+    /// styles of files whose names end with the suffix. if several match, the last one is used.
+    pub suffixes: Vec<(String, ContentStyle)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let color = |color| ContentStyle {
+            foreground_color: Some(color),
+            ..Default::default()
+        };
+        let mut path = color(Color::Green);
+        path.attributes.set(Attribute::Underlined);
+        path.attributes.set(Attribute::Bold);
+        let home = ContentStyle {
+            foreground_color: Some(Color::Cyan),
+            ..path
+        };
+        Self {
+            file: color(Color::Blue),
+            dir: color(Color::Yellow),
+            symlink: Some(color(Color::Grey)),
+            orphan: None,
+            executable: None,
+            error: color(Color::Red),
+            path,
+            home,
+            suffixes: vec![],
+        }
+    }
+}
+
+impl Theme {
+    /// changes the styles set in `ls_colors` (the value of `LS_COLORS`).
+    /// codes tuifile doesn't use and invalid entries are ignored, like `ls` does.
+    pub fn apply_ls_colors(&mut self, ls_colors: &str) {
+        for item in ls_colors.split(':') {
+            let (code, value) = match item.split_once('=') {
+                Some(v) => v,
+                None => continue,
+            };
+            let key = match code {
+                "fi" => "file",
+                "di" => "dir",
+                "ln" => "symlink",
+                "or" => "orphan",
+                "ex" => "executable",
+                _ if code.starts_with('*') => code,
+                _ => continue,
+            };
+            let _ = self.set(key, value);
+        }
+    }
+    /// changes the styles set in the theme file at `path`.
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        for (line_nr, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("{}:{}: {msg}", path.display(), line_nr + 1);
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => return Err(err(format!("expected 'key = value', found '{line}'"))),
+            };
+            self.set(key, value).map_err(err)?;
+        }
+        Ok(())
+    }
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if key == "symlink" && value == "target" {
+            self.symlink = None;
+            return Ok(());
+        }
+        let style = parse_style(value)?;
+        match key {
+            "file" => self.file = style,
+            "dir" => self.dir = style,
+            "symlink" => self.symlink = Some(style),
+            "orphan" => self.orphan = Some(style),
+            "executable" => self.executable = Some(style),
+            "error" => self.error = style,
+            "path" => self.path = style,
+            "home" => self.home = style,
+            _ => match key.strip_prefix('*') {
+                Some(suffix) if !suffix.is_empty() => {
+                    self.suffixes.push((suffix.to_owned(), style))
+                }
+                _ => return Err(format!("unknown key '{key}'")),
+            },
+        }
+        Ok(())
+    }
+    /// the style of an entry in the file list whose metadata was loaded
+    pub fn entry_style(&self, name: &str, more: &DirContentType) -> ContentStyle {
+        match more {
+            DirContentType::Dir { .. } => self.dir,
+            DirContentType::File { metadata } => self.file_style(name, metadata),
+            DirContentType::Symlink { target, .. } => match (target, self.orphan, self.symlink) {
+                (None, Some(orphan), _) => orphan,
+                (_, _, Some(style)) => style,
+                (Some(target), _, None) if target.is_dir() => self.dir,
+                (Some(target), _, None) => self.file_style(name, target),
+                (None, None, None) => self.file,
+            },
+            DirContentType::Err(_) => self.error,
+            DirContentType::Pending | DirContentType::More { .. } => ContentStyle::new(),
+        }
+    }
+    fn file_style(&self, name: &str, metadata: &Metadata) -> ContentStyle {
+        match self.executable {
+            Some(style) if metadata.permissions().mode() & 0o111 != 0 => style,
+            _ => self
+                .suffixes
+                .iter()
+                .rev()
+                .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+                .map_or(self.file, |(_, style)| *style),
+        }
+    }
+}
+
+/// parses `LS_COLORS` codes like `01;34` or words like `bold blue on #202020`
+fn parse_style(value: &str) -> Result<ContentStyle, String> {
+    if value.chars().all(|ch| ch.is_ascii_digit() || ch == ';') {
+        return parse_codes(value).ok_or_else(|| format!("invalid color codes '{value}'"));
+    }
+    let mut style = ContentStyle::new();
+    let mut background = false;
+    for word in value.split_whitespace() {
+        match word {
+            "on" => {
+                background = true;
+                continue;
+            }
+            "bold" => style.attributes.set(Attribute::Bold),
+            "dim" => style.attributes.set(Attribute::Dim),
+            "italic" => style.attributes.set(Attribute::Italic),
+            "underlined" => style.attributes.set(Attribute::Underlined),
+            "reverse" => style.attributes.set(Attribute::Reverse),
+            "crossed_out" => style.attributes.set(Attribute::CrossedOut),
+            _ => {
+                let color = match parse_color(word) {
+                    Some(v) => v,
+                    None => return Err(format!("unknown color or attribute '{word}'")),
+                };
+                if background {
+                    style.background_color = Some(color);
+                } else {
+                    style.foreground_color = Some(color);
+                }
+            }
+        }
+        background = false;
+    }
+    Ok(style)
+}
+
+/// a color name (like `dark_grey`), a number from the 256-color palette, or `#rrggbb`
+fn parse_color(word: &str) -> Option<Color> {
+    if let Some(hex) = word.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let component = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: component(0)?,
+            g: component(2)?,
+            b: component(4)?,
+        });
+    }
+    if let Ok(v) = word.parse() {
+        return Some(Color::AnsiValue(v));
+    }
+    Color::try_from(word).ok()
+}
+
+/// parses SGR codes separated by `;`, including `38;5;<n>` and `38;2;<r>;<g>;<b>` (and 48 for the background)
+fn parse_codes(value: &str) -> Option<ContentStyle> {
+    let mut style = ContentStyle::new();
+    let mut codes = value.split(';');
+    while let Some(code) = codes.next() {
+        // an empty code is the same as 0
+        let code: u8 = if code.is_empty() {
+            0
+        } else {
+            code.parse().ok()?
+        };
+        match code {
+            0 => style = ContentStyle::new(),
+            1 => style.attributes.set(Attribute::Bold),
+            2 => style.attributes.set(Attribute::Dim),
+            3 => style.attributes.set(Attribute::Italic),
+            4 => style.attributes.set(Attribute::Underlined),
+            5 => style.attributes.set(Attribute::SlowBlink),
+            7 => style.attributes.set(Attribute::Reverse),
+            8 => style.attributes.set(Attribute::Hidden),
+            9 => style.attributes.set(Attribute::CrossedOut),
+            30..=37 => style.foreground_color = Some(DARK_COLORS[code as usize - 30]),
+            39 => style.foreground_color = None,
+            40..=47 => style.background_color = Some(DARK_COLORS[code as usize - 40]),
+            49 => style.background_color = None,
+            90..=97 => style.foreground_color = Some(BRIGHT_COLORS[code as usize - 90]),
+            100..=107 => style.background_color = Some(BRIGHT_COLORS[code as usize - 100]),
+            38 | 48 => {
+                let mut next = || -> Option<u8> { codes.next()?.parse().ok() };
+                let color = match next()? {
+                    5 => Color::AnsiValue(next()?),
+                    2 => Color::Rgb {
+                        r: next()?,
+                        g: next()?,
+                        b: next()?,
+                    },
+                    _ => return None,
+                };
+                if code == 38 {
+                    style.foreground_color = Some(color);
+                } else {
+                    style.background_color = Some(color);
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crossterm::style::{Attribute, Color, ContentStyle};
+
+    use super::{parse_codes, parse_style, Theme};
+
+    fn style(foreground: Option<Color>, attributes: &[Attribute]) -> ContentStyle {
+        let mut style = ContentStyle::new();
+        style.foreground_color = foreground;
+        for attribute in attributes {
+            style.attributes.set(*attribute);
+        }
+        style
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(
+            parse_codes("01;34"),
+            Some(style(Some(Color::DarkBlue), &[Attribute::Bold]))
+        );
+        assert_eq!(parse_codes("91"), Some(style(Some(Color::Red), &[])));
+        assert_eq!(
+            parse_codes("38;5;208"),
+            Some(style(Some(Color::AnsiValue(208)), &[]))
+        );
+        let mut background = ContentStyle::new();
+        background.background_color = Some(Color::Rgb { r: 1, g: 2, b: 3 });
+        assert_eq!(parse_codes("48;2;1;2;3"), Some(background));
+        background.background_color = Some(Color::DarkRed);
+        assert_eq!(parse_codes("41"), Some(background));
+        // 0 and empty codes reset everything before them, 39 resets the color
+        assert_eq!(
+            parse_codes("1;31;0;4"),
+            Some(style(None, &[Attribute::Underlined]))
+        );
+        assert_eq!(
+            parse_codes("1;31;;4"),
+            Some(style(None, &[Attribute::Underlined]))
+        );
+        assert_eq!(
+            parse_codes("1;31;39"),
+            Some(style(None, &[Attribute::Bold]))
+        );
+        // unknown codes are ignored
+        assert_eq!(
+            parse_codes("6;32"),
+            Some(style(Some(Color::DarkGreen), &[]))
+        );
+        assert_eq!(parse_codes("38;5"), None);
+        assert_eq!(parse_codes("38;3;1"), None);
+        assert_eq!(parse_codes("38;2;1;2;300"), None);
+        assert_eq!(parse_codes("300"), None);
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            parse_style("bold blue"),
+            Ok(style(Some(Color::Blue), &[Attribute::Bold]))
+        );
+        assert_eq!(
+            parse_style("dark_grey"),
+            Ok(style(Some(Color::DarkGrey), &[]))
+        );
+        assert_eq!(
+            parse_style("bold 208"),
+            Ok(style(Some(Color::AnsiValue(208)), &[Attribute::Bold]))
+        );
+        // a single number is a code, not a color from the palette
+        assert_eq!(parse_style("208"), Ok(ContentStyle::new()));
+        let mut expected = style(Some(Color::Red), &[Attribute::Italic]);
+        expected.background_color = Some(Color::Rgb {
+            r: 0x20,
+            g: 0x20,
+            b: 0xff,
+        });
+        assert_eq!(parse_style("italic red on #2020ff"), Ok(expected));
+        assert_eq!(parse_style(""), Ok(ContentStyle::new()));
+        assert!(parse_style("blurple").is_err());
+        assert!(parse_style("#12345").is_err());
+        assert!(parse_style("#gg0000").is_err());
+        assert!(parse_style("1;38;9").is_err());
+    }
+
+    #[test]
+    fn ls_colors() {
+        let mut theme = Theme::default();
+        let file = theme.file;
+        theme.apply_ls_colors("di=01;34:ln=target:*.rs=31:ex=bogus:tw=30;42:xx:or=:*=32");
+        assert_eq!(theme.dir, style(Some(Color::DarkBlue), &[Attribute::Bold]));
+        assert_eq!(theme.symlink, None);
+        assert_eq!(
+            theme.suffixes,
+            vec![(".rs".to_owned(), style(Some(Color::DarkRed), &[]))]
+        );
+        // invalid and unused entries are skipped
+        assert_eq!(theme.executable, None);
+        assert_eq!(theme.orphan, Some(ContentStyle::new()));
+        assert_eq!(theme.file, file);
+    }
+
+    #[test]
+    fn theme_file() {
+        let path = std::env::temp_dir().join(format!("tuifile-theme-{}", std::process::id()));
+        fs::write(
+            &path,
+            "# comment\n\nfile = green\n  path=bold  \n*.md = 33\n",
+        )
+        .unwrap();
+        let mut theme = Theme::default();
+        assert_eq!(theme.load(&path), Ok(()));
+        assert_eq!(theme.file, style(Some(Color::Green), &[]));
+        assert_eq!(theme.path, style(None, &[Attribute::Bold]));
+        assert_eq!(
+            theme.suffixes,
+            vec![(".md".to_owned(), style(Some(Color::DarkYellow), &[]))]
+        );
+        // errors include the line
+        for (text, error) in [
+            (
+                "file = green\nfile\n",
+                ":2: expected 'key = value', found 'file'",
+            ),
+            ("files = green\n", ":1: unknown key 'files'"),
+            (
+                "file = blurple\n",
+                ":1: unknown color or attribute 'blurple'",
+            ),
+        ] {
+            fs::write(&path, text).unwrap();
+            let result = Theme::default().load(&path);
+            assert_eq!(result, Err(format!("{}{error}", path.display())));
+        }
+        let _ = fs::remove_file(&path);
+    }
+}
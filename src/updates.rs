@@ -48,6 +48,10 @@ pub(crate) trait Updates {
     fn redraw_filebar(&self) -> bool;
     fn dont_redraw_filebar(&mut self);
     fn request_redraw_filebar(&mut self);
+
+    fn redraw_preview(&self) -> bool;
+    fn dont_redraw_preview(&mut self);
+    fn request_redraw_preview(&mut self);
 }
 impl Updates for u32 {
     fn rescan_files(&self) -> bool {
@@ -149,4 +153,13 @@ impl Updates for u32 {
     fn request_redraw_filebar(&mut self) {
         *self |= 0b10000000000;
     }
+    fn redraw_preview(&self) -> bool {
+        0 != self & 0b100000000000
+    }
+    fn dont_redraw_preview(&mut self) {
+        *self ^= 0b100000000000;
+    }
+    fn request_redraw_preview(&mut self) {
+        *self |= 0b100000000000;
+    }
 }
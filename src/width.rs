@@ -0,0 +1,52 @@
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// the number of terminal columns `text` takes up
+pub(crate) fn width(text: &str) -> usize {
+    text.width()
+}
+
+/// the longest start of `text` which fits into `max` columns, and its width.
+/// never splits what is drawn as one character, like an emoji joined with zero width joiners or a letter with combining marks.
+pub(crate) fn truncate(text: &str, max: usize) -> (&str, usize) {
+    let mut width = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        let w = grapheme.width();
+        if width + w > max {
+            return (&text[..i], width);
+        }
+        width += w;
+    }
+    (text, width)
+}
+
+/// like `truncate`, but keeps the end of `text`
+pub(crate) fn truncate_start(text: &str, max: usize) -> (&str, usize) {
+    let mut width = 0;
+    for (i, grapheme) in text.grapheme_indices(true).rev() {
+        let w = grapheme.width();
+        if width + w > max {
+            return (&text[i + grapheme.len()..], width);
+        }
+        width += w;
+    }
+    (text, width)
+}
+
+/// replaces control characters, which would move the cursor or change colors when printed, with escapes like `\n` or `\u{1b}`
+pub(crate) fn escape_control(text: &str) -> Cow<'_, str> {
+    if !text.contains(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for ch in text.chars() {
+        if ch.is_control() {
+            escaped.extend(ch.escape_default());
+        } else {
+            escaped.push(ch);
+        }
+    }
+    Cow::Owned(escaped)
+}